@@ -0,0 +1,290 @@
+use crate::header;
+use crate::parser;
+use crate::subject::Subject;
+use crate::{
+    to_payload, DeliveredMessage, HeaderDeliveredMessage, HeaderMap, NatsParseError, Payload,
+    ProtocolMessage,
+};
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+use std::str::FromStr;
+
+/// A `MSG` borrowing its subject, reply subject and payload from the buffer it was parsed from,
+/// so that it can be handled without allocating
+///
+/// ```rust
+/// extern crate nats_types;
+///
+/// use nats_types::DeliveredMessageRef;
+///
+/// let buf = b"MSG orders.new 7 reply.1 5\r\nhello\r\n";
+/// let msg = DeliveredMessageRef::parse(buf).unwrap();
+/// assert_eq!(msg.subject, "orders.new");
+/// assert_eq!(msg.reply_to, Some("reply.1"));
+/// assert_eq!(msg.payload, &b"hello"[..]);
+///
+/// let owned = msg.to_owned();
+/// assert_eq!(owned.subscription_id, 7);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeliveredMessageRef<'a> {
+    pub subject: &'a str,
+    pub subscription_id: usize,
+    pub reply_to: Option<&'a str>,
+    pub payload: &'a [u8],
+}
+
+impl<'a> DeliveredMessageRef<'a> {
+    /// Parses a delivered message from raw bytes, allowing for arbitrary binary payloads
+    pub fn parse(buf: &'a [u8]) -> Result<DeliveredMessageRef<'a>, NatsParseError> {
+        let (control, start) = parser::control_line(buf, "MSG")?;
+        let hdr = parser::parse_msg_header_ref(control)?;
+        let payload = parser::take_payload(buf, start, hdr.message_len, "MSG")?;
+        Ok(DeliveredMessageRef {
+            subject: hdr.subject,
+            subscription_id: hdr.sid,
+            reply_to: hdr.reply_to,
+            payload,
+        })
+    }
+
+    /// Copies the message into an owned `DeliveredMessage`
+    pub fn to_owned(&self) -> DeliveredMessage {
+        self.with_payload(to_payload(self.payload))
+    }
+
+    /// Converts the message into an owned `DeliveredMessage` whose payload shares the
+    /// allocation of `buf` rather than being copied. Panics if `buf` is not the buffer the
+    /// message was parsed from. Available with the `bytes` feature.
+    #[cfg(feature = "bytes")]
+    pub fn to_shared(&self, buf: &Bytes) -> DeliveredMessage {
+        self.with_payload(buf.slice_ref(self.payload))
+    }
+
+    fn with_payload(&self, payload: Payload) -> DeliveredMessage {
+        DeliveredMessage {
+            subject: Subject::from_validated(self.subject),
+            subscription_id: self.subscription_id,
+            reply_to: self.reply_to.map(str::to_string),
+            payload_size: payload.len(),
+            payload,
+        }
+    }
+}
+
+impl<'a> From<DeliveredMessageRef<'a>> for DeliveredMessage {
+    fn from(msg: DeliveredMessageRef<'a>) -> Self {
+        msg.to_owned()
+    }
+}
+
+/// An `HMSG` borrowing its subject, reply subject, header block and payload from the buffer it
+/// was parsed from. The header block is checked to be well formed when the message is parsed;
+/// individual headers can be looked up without allocating, or the whole block can be decoded
+/// into a `HeaderMap`.
+///
+/// ```rust
+/// extern crate nats_types;
+///
+/// use nats_types::HeaderDeliveredMessageRef;
+///
+/// let buf = b"HMSG orders.new 7 29 34\r\nNATS/1.0\r\nNats-Msg-Id: 42\r\n\r\nhello\r\n";
+/// let msg = HeaderDeliveredMessageRef::parse(buf).unwrap();
+/// assert_eq!(msg.header("Nats-Msg-Id"), Some("42"));
+/// assert_eq!(msg.payload, &b"hello"[..]);
+/// assert_eq!(msg.headers().get("Nats-Msg-Id"), Some("42"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeaderDeliveredMessageRef<'a> {
+    pub subject: &'a str,
+    pub subscription_id: usize,
+    pub reply_to: Option<&'a str>,
+    // validated when parsed, so kept private
+    headers: &'a str,
+    pub payload: &'a [u8],
+}
+
+impl<'a> HeaderDeliveredMessageRef<'a> {
+    /// Parses a header message from raw bytes, allowing for arbitrary binary payloads
+    pub fn parse(buf: &'a [u8]) -> Result<HeaderDeliveredMessageRef<'a>, NatsParseError> {
+        let (control, start) = parser::control_line(buf, "HMSG")?;
+        let hdr = parser::parse_hmsg_header(control)?;
+        let (headers, payload) =
+            parser::split_headers_and_payload(buf, start, hdr.header_len, hdr.total_len, "HMSG")?;
+        let headers =
+            header::validate(headers).map_err(|e| e.with_verb("HMSG").offset_by(start))?;
+        Ok(HeaderDeliveredMessageRef {
+            subject: hdr.subject,
+            subscription_id: hdr.sid,
+            reply_to: hdr.reply_to,
+            headers,
+            payload,
+        })
+    }
+
+    /// The serialized header block, including its version line and trailing empty line
+    pub fn raw_headers(&self) -> &'a [u8] {
+        self.headers.as_bytes()
+    }
+
+    /// The first value of the given header, if present
+    pub fn header(&self, key: &str) -> Option<&'a str> {
+        header::get_raw(self.headers, key)
+    }
+
+    /// Decodes the header block
+    pub fn headers(&self) -> HeaderMap {
+        HeaderMap::from_str(self.headers).expect("header block was validated when parsed")
+    }
+
+    /// Copies the message into an owned `HeaderDeliveredMessage`
+    pub fn to_owned(&self) -> HeaderDeliveredMessage {
+        self.with_payload(to_payload(self.payload))
+    }
+
+    /// Converts the message into an owned `HeaderDeliveredMessage` whose payload shares the
+    /// allocation of `buf` rather than being copied. Panics if `buf` is not the buffer the
+    /// message was parsed from. Available with the `bytes` feature.
+    #[cfg(feature = "bytes")]
+    pub fn to_shared(&self, buf: &Bytes) -> HeaderDeliveredMessage {
+        self.with_payload(buf.slice_ref(self.payload))
+    }
+
+    fn with_payload(&self, payload: Payload) -> HeaderDeliveredMessage {
+        HeaderDeliveredMessage {
+            subject: Subject::from_validated(self.subject),
+            subscription_id: self.subscription_id,
+            reply_to: self.reply_to.map(str::to_string),
+            header_size: self.headers.len(),
+            total_size: self.headers.len() + payload.len(),
+            headers: self.headers(),
+            payload,
+        }
+    }
+}
+
+impl<'a> From<HeaderDeliveredMessageRef<'a>> for HeaderDeliveredMessage {
+    fn from(msg: HeaderDeliveredMessageRef<'a>) -> Self {
+        msg.to_owned()
+    }
+}
+
+/// A protocol message in which the messages delivered to subscriptions borrow from the buffer
+/// they were parsed from. Every other operation is parsed into its owned form, boxed so that
+/// the common case stays small.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProtocolMessageRef<'a> {
+    Message(DeliveredMessageRef<'a>),
+    HeaderMessage(HeaderDeliveredMessageRef<'a>),
+    Other(Box<ProtocolMessage>),
+}
+
+impl<'a> ProtocolMessageRef<'a> {
+    /// Parses a single protocol message from raw bytes
+    pub fn parse(buf: &'a [u8]) -> Result<ProtocolMessageRef<'a>, NatsParseError> {
+        if buf.starts_with(b"HMSG") {
+            HeaderDeliveredMessageRef::parse(buf).map(ProtocolMessageRef::HeaderMessage)
+        } else if buf.starts_with(b"MSG") {
+            DeliveredMessageRef::parse(buf).map(ProtocolMessageRef::Message)
+        } else {
+            ProtocolMessage::parse(buf).map(|m| ProtocolMessageRef::Other(Box::new(m)))
+        }
+    }
+
+    /// Copies the message into an owned `ProtocolMessage`
+    pub fn to_owned(&self) -> ProtocolMessage {
+        match self {
+            ProtocolMessageRef::Message(m) => ProtocolMessage::Message(m.to_owned()),
+            ProtocolMessageRef::HeaderMessage(m) => ProtocolMessage::HeaderMessage(m.to_owned()),
+            ProtocolMessageRef::Other(m) => (**m).clone(),
+        }
+    }
+
+    /// Converts the message into an owned `ProtocolMessage`. The payload of a delivered message
+    /// shares the allocation of `buf`, while every other operation, `PUB` and `HPUB` included,
+    /// was already copied when parsed. Panics if `buf` is not the buffer the message was parsed
+    /// from. Available with the `bytes` feature.
+    #[cfg(feature = "bytes")]
+    pub fn to_shared(self, buf: &Bytes) -> ProtocolMessage {
+        match self {
+            ProtocolMessageRef::Message(m) => ProtocolMessage::Message(m.to_shared(buf)),
+            ProtocolMessageRef::HeaderMessage(m) => {
+                ProtocolMessage::HeaderMessage(m.to_shared(buf))
+            }
+            ProtocolMessageRef::Other(m) => *m,
+        }
+    }
+}
+
+impl<'a> From<ProtocolMessageRef<'a>> for ProtocolMessage {
+    fn from(msg: ProtocolMessageRef<'a>) -> Self {
+        match msg {
+            ProtocolMessageRef::Other(m) => *m,
+            m => m.to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DeliveredMessageRef, HeaderDeliveredMessageRef, ProtocolMessageRef};
+    use crate::{DeliveredMessage, HeaderDeliveredMessage, ParseErrorKind, ProtocolMessage};
+
+    #[test]
+    fn delivered_ref_borrows_buffer() {
+        let buf = b"MSG workdispatch 1 reply.topic 4\r\n\x00\xff\r\n\r\n".to_vec();
+        let msg = DeliveredMessageRef::parse(&buf).unwrap();
+        assert_eq!(msg.payload, &b"\x00\xff\r\n"[..]);
+        assert!(buf.as_ptr_range().contains(&msg.subject.as_ptr()));
+        assert!(buf.as_ptr_range().contains(&msg.payload.as_ptr()));
+        assert_eq!(msg.to_owned(), DeliveredMessage::parse(&buf).unwrap());
+
+        let err = DeliveredMessageRef::parse(b"MSG orders.* 1 0\r\n\r\n").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::MalformedControlLine);
+        let err = DeliveredMessageRef::parse(b"MSG orders 1 5\r\nhel").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::Incomplete);
+    }
+
+    #[test]
+    fn header_delivered_ref() {
+        let raw = "HMSG workdispatch 1 reply.topic 43 48\r\nNATS/1.0 100 Idle Heartbeat\r\nA: 1\r\nA: 2\r\n\r\nhello\r\n";
+        let msg = HeaderDeliveredMessageRef::parse(raw.as_bytes()).unwrap();
+        assert_eq!(msg.header("A"), Some("1"));
+        assert_eq!(msg.header("B"), None);
+        assert_eq!(msg.headers().status(), Some(100));
+        assert_eq!(msg.raw_headers().len(), 43);
+        assert_eq!(
+            msg.to_owned(),
+            HeaderDeliveredMessage::parse(raw.as_bytes()).unwrap()
+        );
+
+        let err = HeaderDeliveredMessageRef::parse(b"HMSG a 1 15 15\r\nNATS/1.0\r\nA\r\n\r\n\r\n")
+            .unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::MalformedHeaders);
+        assert_eq!(err.offset(), 26);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn shared_payloads() {
+        let buf = bytes::Bytes::from_static(b"MSG a 1 2\r\nhi\r\n");
+        let msg = DeliveredMessageRef::parse(&buf).unwrap().to_shared(&buf);
+        assert_eq!(msg.payload, &b"hi"[..]);
+        assert_eq!(msg.payload.as_ptr(), buf[11..].as_ptr());
+    }
+
+    #[test]
+    fn protocol_ref_falls_back_to_owned() {
+        let msg = ProtocolMessageRef::parse(b"MSG a 1 2\r\nhi\r\n").unwrap();
+        assert!(matches!(msg, ProtocolMessageRef::Message(_)));
+        assert_eq!(
+            ProtocolMessage::from(msg),
+            ProtocolMessage::parse(b"MSG a 1 2\r\nhi\r\n").unwrap()
+        );
+        let msg = ProtocolMessageRef::parse(b"PING\r\n").unwrap();
+        assert_eq!(
+            msg,
+            ProtocolMessageRef::Other(Box::new(ProtocolMessage::Ping))
+        );
+    }
+}
@@ -0,0 +1,166 @@
+#[cfg(feature = "bytes")]
+use crate::ProtocolMessageRef;
+use crate::{Encode, ProtocolDecoder, ProtocolMessage};
+#[cfg(not(feature = "bytes"))]
+use bytes::Buf;
+use bytes::{BufMut, BytesMut};
+use std::io;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// A codec for use with `tokio_util::codec::Framed` and friends that turns a byte stream, such
+/// as a `TcpStream` connected to a NATS server, into a stream of `ProtocolMessage`s and a sink
+/// accepting them. Available with the `tokio-codec` feature.
+///
+/// With the `bytes` feature, the payloads of delivered messages (`MSG` and `HMSG`) share the
+/// allocation of the read buffer instead of being copied. `PUB` and `HPUB`, which a server never
+/// sends to a client, are still decoded into copied payloads.
+///
+/// Parse failures are reported as `std::io::Error`s of kind `InvalidData` wrapping the
+/// underlying `NatsParseError`.
+#[derive(Debug, Clone, Default)]
+pub struct NatsCodec {
+    decoder: ProtocolDecoder,
+}
+
+impl NatsCodec {
+    /// Creates a new codec
+    pub fn new() -> NatsCodec {
+        NatsCodec::default()
+    }
+}
+
+impl Decoder for NatsCodec {
+    type Item = ProtocolMessage;
+    type Error = io::Error;
+
+    #[cfg(not(feature = "bytes"))]
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<ProtocolMessage>, io::Error> {
+        match self.decoder.decode(src) {
+            Ok(Some((msg, consumed))) => {
+                src.advance(consumed);
+                Ok(Some(msg))
+            }
+            Ok(None) => Ok(None),
+            Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        }
+    }
+
+    // delivered payloads are sliced out of the frame rather than copied
+    #[cfg(feature = "bytes")]
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<ProtocolMessage>, io::Error> {
+        let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+        match self.decoder.next_frame_len(src).map_err(invalid)? {
+            Some(len) => {
+                let frame = src.split_to(len).freeze();
+                ProtocolMessageRef::parse(&frame)
+                    .map(|m| Some(m.to_shared(&frame)))
+                    .map_err(invalid)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl Encoder<ProtocolMessage> for NatsCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: ProtocolMessage, dst: &mut BytesMut) -> Result<(), io::Error> {
+        let start = dst.len();
+        dst.reserve(item.encoded_len());
+        // don't leave a partially written message behind
+        item.encode_into(&mut dst.writer())
+            .inspect_err(|_| dst.truncate(start))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::NatsCodec;
+    use crate::{
+        DeliveredMessage, HeaderMap, HeaderPublishMessage, ProtocolMessage, SubscribeMessage,
+    };
+    use bytes::BytesMut;
+    use futures::{SinkExt, StreamExt};
+    use tokio::io::AsyncWriteExt;
+    use tokio_util::codec::{Decoder, FramedRead, FramedWrite};
+
+    #[tokio::test]
+    async fn codec_duplex_roundtrip() {
+        let (client, server) = tokio::io::duplex(64);
+        let mut writer = FramedWrite::new(client, NatsCodec::new());
+        let mut reader = FramedRead::new(server, NatsCodec::new());
+
+        let mut headers = HeaderMap::new();
+        headers.append("Nats-Msg-Id", "1").unwrap();
+        let sent = vec![
+            ProtocolMessage::Subscribe(
+                SubscribeMessage::new("FOO".parse().unwrap(), None, 1).unwrap(),
+            ),
+            ProtocolMessage::HeaderPublish(
+                HeaderPublishMessage::new(
+                    "FOO".parse().unwrap(),
+                    Some("INBOX.1".to_string()),
+                    headers,
+                    vec![0xff; 200],
+                )
+                .unwrap(),
+            ),
+            ProtocolMessage::Message(DeliveredMessage::new(
+                "FOO".parse().unwrap(),
+                1,
+                None,
+                b"Hello\r\nWorld".to_vec(),
+            )),
+            ProtocolMessage::Ping,
+        ];
+
+        let expected = sent.clone();
+        let write = tokio::spawn(async move {
+            for msg in sent {
+                writer.send(msg).await.unwrap();
+            }
+        });
+        for msg in expected {
+            assert_eq!(reader.next().await.unwrap().unwrap(), msg);
+        }
+        write.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn codec_reports_parse_errors() {
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut reader = FramedRead::new(server, NatsCodec::new());
+        client.write_all(b"+OK\r\nBOGUS\r\n").await.unwrap();
+
+        assert_eq!(reader.next().await.unwrap().unwrap(), ProtocolMessage::Ok);
+        let err = reader.next().await.unwrap().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn codec_waits_for_payload() {
+        let mut codec = NatsCodec::new();
+        let mut buf = BytesMut::from(&b"MSG FOO 1 5\r\nHel"[..]);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+        buf.extend_from_slice(b"lo\r\nPONG\r\n");
+        assert!(codec.decode(&mut buf).unwrap().is_some());
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(ProtocolMessage::Pong));
+        assert!(buf.is_empty());
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn codec_shares_payloads() {
+        let mut buf = BytesMut::from(&b"MSG FOO 1 5\r\nHello\r\nPING\r\n"[..]);
+        let read = buf.as_ptr_range();
+        match NatsCodec::new().decode(&mut buf).unwrap() {
+            Some(ProtocolMessage::Message(m)) => {
+                assert_eq!(m.payload, &b"Hello"[..]);
+                assert!(read.contains(&m.payload.as_ptr()));
+                assert_eq!(m.clone().payload.as_ptr(), m.payload.as_ptr());
+            }
+            other => panic!("expected MSG, got {:?}", other),
+        }
+        assert_eq!(&buf[..], b"PING\r\n");
+    }
+}
@@ -0,0 +1,394 @@
+use crate::nkeys::{verify_signature, KeyKind, KeyPair, NkeyError};
+use crate::{ConnectionInformation, ConnectionInformationBuilder};
+use data_encoding::BASE64URL_NOPAD;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Indicates a creds file or user JWT could not be used
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CredentialsError {
+    /// The creds file has no `NATS USER JWT` block
+    MissingJwt,
+    /// The creds file has no `USER NKEY SEED` block
+    MissingSeed,
+    /// The seed could not be decoded
+    InvalidSeed(NkeyError),
+    /// The JWT is not made up of three base64url encoded parts
+    MalformedJwt,
+    /// The JWT header or claims could not be deserialized
+    InvalidClaims(serde_json::Error),
+    /// The JWT uses a signing algorithm other than `ed25519-nkey`, e.g. the `ed25519` of v1
+    /// JWTs, whose claims have a different layout
+    UnsupportedAlgorithm(String),
+    /// The JWT was not signed by its issuer
+    InvalidSignature,
+    /// The JWT holds claims for something other than a user
+    NotUserClaims,
+    /// The seed does not belong to the user the JWT was issued to
+    KeyMismatch,
+}
+
+impl Error for CredentialsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CredentialsError::InvalidSeed(e) => Some(e),
+            CredentialsError::InvalidClaims(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Display for CredentialsError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            CredentialsError::MissingJwt => write!(f, "Invalid creds - no user JWT"),
+            CredentialsError::MissingSeed => write!(f, "Invalid creds - no nkey seed"),
+            CredentialsError::InvalidSeed(e) => write!(f, "Invalid creds - {}", e),
+            CredentialsError::MalformedJwt => write!(f, "Invalid JWT - malformed token"),
+            CredentialsError::InvalidClaims(e) => write!(f, "Invalid JWT - {}", e),
+            CredentialsError::UnsupportedAlgorithm(alg) => {
+                write!(f, "Invalid JWT - unsupported algorithm '{}'", alg)
+            }
+            CredentialsError::InvalidSignature => {
+                write!(f, "Invalid JWT - not signed by its issuer")
+            }
+            CredentialsError::NotUserClaims => write!(f, "Invalid JWT - not a user JWT"),
+            CredentialsError::KeyMismatch => {
+                write!(f, "Invalid creds - seed does not match the JWT subject")
+            }
+        }
+    }
+}
+
+/// A publish or subscribe permission. Subscribe permissions may name a queue group after the
+/// subject, separated by a space.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Permission {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<String>,
+}
+
+/// Permits the user to publish to the reply subjects of requests it receives
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResponsePermission {
+    /// The number of responses allowed per request
+    pub max: i64,
+    /// How long the permission lasts, in nanoseconds
+    pub ttl: i64,
+}
+
+/// The NATS specific portion of user claims: permissions and limits. Limits of `-1` mean
+/// unlimited.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UserPermissionLimits {
+    #[serde(rename = "pub", default)]
+    pub publish: Permission,
+    #[serde(rename = "sub", default)]
+    pub subscribe: Permission,
+    #[serde(rename = "resp", default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<ResponsePermission>,
+    /// The maximum number of subscriptions
+    #[serde(default = "no_limit")]
+    pub subs: i64,
+    /// The maximum number of bytes of payload
+    #[serde(default = "no_limit")]
+    pub data: i64,
+    /// The maximum size of a single message payload
+    #[serde(default = "no_limit")]
+    pub payload: i64,
+    /// Indicates that the JWT alone is sufficient to connect, without signing the nonce
+    #[serde(default)]
+    pub bearer_token: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_connection_types: Vec<String>,
+    /// The account of the issuer when the JWT was issued with an account signing key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issuer_account: Option<String>,
+    #[serde(rename = "type")]
+    pub claim_type: String,
+    #[serde(default)]
+    pub version: u32,
+    /// Any fields not known to this crate
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+fn no_limit() -> i64 {
+    -1
+}
+
+/// The claims of a user JWT
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UserClaims {
+    #[serde(default)]
+    pub jti: String,
+    /// Issued at, in seconds since the Unix epoch
+    #[serde(default)]
+    pub iat: u64,
+    /// The public key of the account (or account signing key) that issued the JWT
+    pub iss: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The public key of the user
+    pub sub: String,
+    /// Expires at, in seconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exp: Option<u64>,
+    /// Not valid before, in seconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<u64>,
+    pub nats: UserPermissionLimits,
+}
+
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: String,
+}
+
+/// A decoded user JWT whose signature has been verified against its issuer. Available with the
+/// `nkeys` feature.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserJwt {
+    token: String,
+    claims: UserClaims,
+}
+
+impl UserJwt {
+    /// Decodes a user JWT, verifying that it was signed by the account in its `iss` claim
+    pub fn decode(token: &str) -> Result<UserJwt, CredentialsError> {
+        let token = token.trim();
+        let mut parts = token.split('.');
+        let (header, claims, signature) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(h), Some(c), Some(s)) if parts.next().is_none() => (h, c, s),
+            _ => return Err(CredentialsError::MalformedJwt),
+        };
+        let decode = |part: &str| {
+            BASE64URL_NOPAD
+                .decode(part.as_bytes())
+                .map_err(|_| CredentialsError::MalformedJwt)
+        };
+
+        let header: JwtHeader =
+            serde_json::from_slice(&decode(header)?).map_err(CredentialsError::InvalidClaims)?;
+        if header.alg != "ed25519-nkey" {
+            return Err(CredentialsError::UnsupportedAlgorithm(header.alg));
+        }
+        let claims: UserClaims =
+            serde_json::from_slice(&decode(claims)?).map_err(CredentialsError::InvalidClaims)?;
+        if claims.nats.claim_type != "user" {
+            return Err(CredentialsError::NotUserClaims);
+        }
+
+        let signed = &token[..token.len() - signature.len() - 1];
+        match verify_signature(&claims.iss, signed.as_bytes(), &decode(signature)?) {
+            Ok(KeyKind::Account) => Ok(UserJwt {
+                token: token.to_string(),
+                claims,
+            }),
+            _ => Err(CredentialsError::InvalidSignature),
+        }
+    }
+
+    /// The encoded JWT, as sent in the `jwt` of a `CONNECT`
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// The claims of the JWT
+    pub fn claims(&self) -> &UserClaims {
+        &self.claims
+    }
+
+    /// The account the user belongs to
+    pub fn account(&self) -> &str {
+        self.claims
+            .nats
+            .issuer_account
+            .as_deref()
+            .unwrap_or(&self.claims.iss)
+    }
+
+    /// The time at which the JWT expires, if it does
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        self.claims
+            .exp
+            .map(|exp| UNIX_EPOCH + Duration::from_secs(exp))
+    }
+
+    /// Indicates whether the JWT has expired
+    pub fn is_expired(&self) -> bool {
+        self.expires_at()
+            .is_some_and(|exp| exp <= SystemTime::now())
+    }
+}
+
+/// The contents of a `.creds` file: a user JWT and the nkey seed of that user, each wrapped in
+/// `-----BEGIN ...-----` and `------END ...------` lines. Available with the `nkeys` feature.
+#[derive(Debug)]
+pub struct Credentials {
+    jwt: UserJwt,
+    key_pair: KeyPair,
+}
+
+impl Credentials {
+    /// Parses a creds file, verifying the JWT and that the seed belongs to its user
+    pub fn parse(contents: &str) -> Result<Credentials, CredentialsError> {
+        let jwt = block(contents, "JWT").ok_or(CredentialsError::MissingJwt)?;
+        let seed = block(contents, "SEED").ok_or(CredentialsError::MissingSeed)?;
+        let jwt = UserJwt::decode(jwt)?;
+        let key_pair = KeyPair::from_seed(seed).map_err(CredentialsError::InvalidSeed)?;
+        if key_pair.kind() != KeyKind::User || key_pair.public_key() != jwt.claims.sub {
+            return Err(CredentialsError::KeyMismatch);
+        }
+        Ok(Credentials { jwt, key_pair })
+    }
+
+    /// The user JWT, verified against its issuer
+    pub fn jwt(&self) -> &UserJwt {
+        &self.jwt
+    }
+
+    /// The key pair of the user, for signing the server's nonce
+    pub fn key_pair(&self) -> &KeyPair {
+        &self.key_pair
+    }
+}
+
+// Finds the first non-empty line following a `BEGIN` line mentioning the given label
+fn block<'a>(contents: &'a str, label: &str) -> Option<&'a str> {
+    let mut lines = contents.lines().map(str::trim);
+    lines.find(|l| l.starts_with("---") && l.contains("BEGIN") && l.contains(label))?;
+    lines
+        .find(|l| !l.is_empty())
+        .filter(|l| !l.starts_with("---"))
+}
+
+impl FromStr for Credentials {
+    type Err = CredentialsError;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        Credentials::parse(s)
+    }
+}
+
+impl ConnectionInformation {
+    /// Authenticates with a user JWT by setting the JWT and the signature of the server's
+    /// nonce. Available with the `nkeys` feature.
+    pub fn sign_nonce_with_credentials(&mut self, creds: &Credentials, nonce: &str) {
+        self.jwt = Some(creds.jwt.token().to_string());
+        self.sig = Some(creds.key_pair.sign_nonce(nonce));
+    }
+}
+
+impl ConnectionInformationBuilder {
+    /// Authenticates with a user JWT by signing the server's nonce. Available with the `nkeys`
+    /// feature.
+    pub fn credentials(self, creds: &Credentials, nonce: &str) -> Self {
+        self.jwt(creds.jwt.token(), creds.key_pair.sign_nonce(nonce))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Credentials, CredentialsError, UserJwt};
+    use crate::ConnectionInformation;
+    use data_encoding::BASE64URL_NOPAD;
+
+    // a user JWT issued by the account seed used in the nkeys tests, produced with OpenSSL
+    const JWT: &str = "eyJ0eXAiOiJKV1QiLCJhbGciOiJlZDI1NTE5LW5rZXkifQ.eyJqdGkiOiI3WlhKMk5RU05SRlpRU1dCM0tBVjdUM1dVRlVWS1ZMTEJTTk5TVlNHVlpRRTROUVFHTVlBIiwiaWF0IjoxNzAwMDAwMDAwLCJpc3MiOiJBQUYzWU5ES0s1VEhZT0FCRUM2Wlk3Nlg0VU9TWVg2NzVJMzQyTDIzNlFDM0ZSVjdONFdYUkZMWiIsIm5hbWUiOiJ3b3JrZXIiLCJzdWIiOiJVQjQzS1ZST1I3VEZKNktBUENZUkYyRkpST1RaQUg0RkhMVEpMUFdYNERSWkNDNU5BU0xHSkJGRSIsImV4cCI6NDEwMjQ0NDgwMCwibmF0cyI6eyJwdWIiOnsiYWxsb3ciOlsib3JkZXJzLj4iXSwiZGVueSI6WyJvcmRlcnMuc2VjcmV0Il19LCJzdWIiOnsiYWxsb3ciOlsiX0lOQk9YLj4iLCJvcmRlcnMuKiJdfSwicmVzcCI6eyJtYXgiOjEsInR0bCI6NTAwMDAwMDAwMH0sInN1YnMiOjEwMCwiZGF0YSI6LTEsInBheWxvYWQiOjEwNDg1NzYsInR5cGUiOiJ1c2VyIiwidmVyc2lvbiI6Mn19.9PR-sAD5iHEs97h3RzQfIv17Q0Jq2tUVvtsL2fclmcbqaqmF1vuzQ90atk1CkOrMqmBz_cxU2YpyhLFcNSYjDA";
+    const USER_SEED: &str = "SUAACAQDAQCQMBYIBEFAWDANBYHRAEISCMKBKFQXDAMRUGY4DUPB6IC5CQ";
+    const ACCOUNT_SEED: &str = "SAAGIZLGM5UGS2TLNRWW433QOFZHG5DVOZ3XQ6L2PN6H27T7QCAYFA5W7M";
+
+    fn creds_file(jwt: &str, seed: &str) -> String {
+        format!(
+            "-----BEGIN NATS USER JWT-----\n{}\n------END NATS USER JWT------\n\n\
+             ************************* IMPORTANT *************************\n\
+             NKEY Seed printed below can be used to sign and prove identity.\n\n\
+             -----BEGIN USER NKEY SEED-----\n{}\n------END USER NKEY SEED------\n",
+            jwt, seed
+        )
+    }
+
+    #[test]
+    fn decode_user_jwt() {
+        let jwt = UserJwt::decode(JWT).unwrap();
+        let claims = jwt.claims();
+        assert_eq!(claims.name.as_deref(), Some("worker"));
+        assert_eq!(
+            claims.sub,
+            "UB43KVROR7TFJ6KAPCYRF2FJROTZAH4FHLTJLPWX4DRZCC5NASLGJBFE"
+        );
+        assert_eq!(jwt.account(), claims.iss);
+        assert_eq!(claims.nats.publish.allow, ["orders.>"]);
+        assert_eq!(claims.nats.publish.deny, ["orders.secret"]);
+        assert_eq!(claims.nats.subscribe.allow, ["_INBOX.>", "orders.*"]);
+        assert_eq!(claims.nats.response.as_ref().unwrap().max, 1);
+        assert_eq!(claims.nats.subs, 100);
+        assert_eq!(claims.nats.data, -1);
+        assert_eq!(claims.nats.payload, 1_048_576);
+        assert_eq!(claims.nats.version, 2);
+        assert!(!jwt.is_expired());
+    }
+
+    #[test]
+    fn reject_tampered_jwt() {
+        let mut parts: Vec<&str> = JWT.split('.').collect();
+        let other = "eyJpc3MiOiJBQUYzWU5ES0s1VEhZT0FCRUM2Wlk3Nlg0VU9TWVg2NzVJMzQyTDIzNlFDM0ZSVjdONFdYUkZMWiIsInN1YiI6IlVCNDNLVlJPUjdURko2S0FQQ1lSRjJGSlJPVFpBSDRGSExUSkxQV1g0RFJaQ0M1TkFTTEdKQkZFIiwibmF0cyI6eyJ0eXBlIjoidXNlciJ9fQ";
+        parts[1] = other;
+        assert!(matches!(
+            UserJwt::decode(&parts.join(".")),
+            Err(CredentialsError::InvalidSignature)
+        ));
+        assert!(matches!(
+            UserJwt::decode("abc.def"),
+            Err(CredentialsError::MalformedJwt)
+        ));
+
+        // v1 JWTs are not supported
+        let v1_header = BASE64URL_NOPAD.encode(br#"{"typ":"jwt","alg":"ed25519"}"#);
+        parts = JWT.split('.').collect();
+        parts[0] = &v1_header;
+        match UserJwt::decode(&parts.join(".")) {
+            Err(CredentialsError::UnsupportedAlgorithm(alg)) => assert_eq!(alg, "ed25519"),
+            other => panic!("expected an unsupported algorithm, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_creds_file() {
+        let creds: Credentials = creds_file(JWT, USER_SEED).parse().unwrap();
+        assert_eq!(creds.jwt().token(), JWT);
+        assert_eq!(creds.key_pair().public_key(), creds.jwt().claims().sub);
+
+        let connect = ConnectionInformation::builder()
+            .credentials(&creds, "PXoWU7zWAMt75FY")
+            .build();
+        assert_eq!(connect.jwt.as_deref(), Some(JWT));
+        assert!(connect.sig.is_some());
+        assert_eq!(connect.nkey, None);
+
+        assert!(matches!(
+            Credentials::parse(&creds_file(JWT, ACCOUNT_SEED)),
+            Err(CredentialsError::KeyMismatch)
+        ));
+        assert!(matches!(
+            Credentials::parse("-----BEGIN NATS USER JWT-----\n"),
+            Err(CredentialsError::MissingJwt)
+        ));
+        let no_seed = format!(
+            "-----BEGIN NATS USER JWT-----\n{}\n------END NATS USER JWT------\n",
+            JWT
+        );
+        assert!(matches!(
+            Credentials::parse(&no_seed),
+            Err(CredentialsError::MissingSeed)
+        ));
+    }
+}
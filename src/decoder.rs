@@ -0,0 +1,320 @@
+use crate::parser;
+use crate::{NatsParseError, ParseErrorKind, ProtocolMessage, ProtocolMessageRef};
+
+/// An incremental decoder for a stream of protocol messages, such as the output of a NATS
+/// server read off a socket in arbitrary chunks.
+///
+/// The decoder is handed the unconsumed portion of the read buffer. When the buffer holds a
+/// complete message, the message is returned along with the number of bytes it occupied so the
+/// caller can discard them before decoding the next one. When the buffer ends part way through
+/// a message (including part way through the payload of a `MSG`), `Ok(None)` is returned and the
+/// caller should read more data and call `decode` again with the extended buffer.
+///
+/// ```rust
+/// extern crate nats_types;
+///
+/// use nats_types::{ProtocolDecoder, ProtocolMessage};
+///
+/// let mut decoder = ProtocolDecoder::new();
+/// let mut buf = b"PING\r\nMSG FOO 1 11\r\nHello".to_vec();
+///
+/// let (msg, consumed) = decoder.decode(&buf).unwrap().unwrap();
+/// assert_eq!(msg, ProtocolMessage::Ping);
+/// buf.drain(..consumed);
+///
+/// assert!(decoder.decode(&buf).unwrap().is_none());
+/// buf.extend_from_slice(b" NATS!\r\n");
+/// let (msg, consumed) = decoder.decode(&buf).unwrap().unwrap();
+/// assert_eq!(consumed, buf.len());
+/// if let ProtocolMessage::Message(m) = msg {
+///     assert_eq!(m.payload, &b"Hello NATS!"[..]);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ProtocolDecoder {
+    max_control_line: usize,
+    max_payload: usize,
+    // length of the message at the front of the buffer, once its control line has been read
+    frame_len: Option<usize>,
+}
+
+/// The default maximum length of a control line accepted by a `ProtocolDecoder`. This is
+/// considerably larger than the server's own limit on client control lines, as `INFO` messages
+/// from large clusters can carry long lists of URLs.
+pub const DEFAULT_MAX_CONTROL_LINE: usize = 64 * 1024;
+
+/// The default maximum size of the body (headers and payload) of a message accepted by a
+/// `ProtocolDecoder`, matching the largest `max_payload` a server can be configured with.
+pub const DEFAULT_MAX_PAYLOAD: usize = 64 * 1024 * 1024;
+
+impl Default for ProtocolDecoder {
+    fn default() -> Self {
+        ProtocolDecoder::with_limits(DEFAULT_MAX_CONTROL_LINE, DEFAULT_MAX_PAYLOAD)
+    }
+}
+
+impl ProtocolDecoder {
+    /// Creates a new decoder accepting control lines of up to `DEFAULT_MAX_CONTROL_LINE` bytes
+    pub fn new() -> ProtocolDecoder {
+        ProtocolDecoder::default()
+    }
+
+    /// Creates a new decoder that fails with `ParseErrorKind::ControlLineTooLong` rather than
+    /// buffering a control line (excluding its CRLF) longer than the given number of bytes
+    pub fn with_max_control_line(max_control_line: usize) -> ProtocolDecoder {
+        ProtocolDecoder::with_limits(max_control_line, DEFAULT_MAX_PAYLOAD)
+    }
+
+    /// Creates a new decoder that also fails with `ParseErrorKind::PayloadTooLarge` rather than
+    /// buffering a message whose declared body exceeds the given number of bytes
+    pub fn with_limits(max_control_line: usize, max_payload: usize) -> ProtocolDecoder {
+        ProtocolDecoder {
+            max_control_line,
+            max_payload,
+            frame_len: None,
+        }
+    }
+
+    /// Attempts to decode the message at the front of the buffer, returning the message and the
+    /// number of bytes consumed, or `None` if more data is required
+    pub fn decode(
+        &mut self,
+        buf: &[u8],
+    ) -> Result<Option<(ProtocolMessage, usize)>, NatsParseError> {
+        let frame_len = match self.next_frame_len(buf)? {
+            Some(len) => len,
+            None => return Ok(None),
+        };
+        ProtocolMessage::parse(&buf[..frame_len]).map(|m| Some((m, frame_len)))
+    }
+
+    /// Like `decode`, but the messages delivered to subscriptions borrow from the buffer rather
+    /// than being copied out of it. The buffer must not be advanced until they are dropped.
+    pub fn decode_ref<'a>(
+        &mut self,
+        buf: &'a [u8],
+    ) -> Result<Option<(ProtocolMessageRef<'a>, usize)>, NatsParseError> {
+        let frame_len = match self.next_frame_len(buf)? {
+            Some(len) => len,
+            None => return Ok(None),
+        };
+        ProtocolMessageRef::parse(&buf[..frame_len]).map(|m| Some((m, frame_len)))
+    }
+
+    // Determines the length of the message at the front of the buffer, remembering it while
+    // the rest of the message is awaited
+    pub(crate) fn next_frame_len(&mut self, buf: &[u8]) -> Result<Option<usize>, NatsParseError> {
+        let frame_len = match self.frame_len {
+            Some(len) => len,
+            None => match frame_len(buf, self.max_control_line, self.max_payload)? {
+                Some(len) => len,
+                None => return Ok(None),
+            },
+        };
+
+        if buf.len() < frame_len {
+            self.frame_len = Some(frame_len);
+            return Ok(None);
+        }
+
+        self.frame_len = None;
+        Ok(Some(frame_len))
+    }
+}
+
+// Determines the total length of the message at the front of the buffer from its control line,
+// or `None` if the control line has not been fully received
+fn frame_len(
+    buf: &[u8],
+    max_control_line: usize,
+    max_payload: usize,
+) -> Result<Option<usize>, NatsParseError> {
+    let control = match parser::split_control_line(buf) {
+        Some((control, _)) if control.len() <= max_control_line => control,
+        None if buf.len() <= max_control_line => return Ok(None),
+        _ => {
+            return Err(NatsParseError::new(
+                ParseErrorKind::ControlLineTooLong,
+                format!(
+                    "Failed to decode message - control line exceeds {} bytes",
+                    max_control_line
+                ),
+            )
+            .at(max_control_line))
+        }
+    };
+    let control_len = control.len() + 2;
+
+    let body_len = if control.starts_with(b"HMSG") {
+        parser::parse_hmsg_header(control)?.total_len
+    } else if control.starts_with(b"HPUB") {
+        parser::parse_hpub_header(control)?.total_len
+    } else if control.starts_with(b"MSG") {
+        parser::parse_msg_header(control)?.message_len
+    } else if control.starts_with(b"PUB") {
+        parser::parse_pub_header(control)?.message_len
+    } else {
+        return Ok(Some(control_len));
+    };
+    if body_len > max_payload {
+        return Err(NatsParseError::new(
+            ParseErrorKind::PayloadTooLarge,
+            format!(
+                "Failed to decode message - declared size of {} bytes exceeds {} bytes",
+                body_len, max_payload
+            ),
+        )
+        .at(control.len()));
+    }
+    // the declared size comes from the peer, so it may be large enough to overflow
+    match control_len
+        .checked_add(body_len)
+        .and_then(|len| len.checked_add(2))
+    {
+        Some(len) => Ok(Some(len)),
+        None => Err(NatsParseError::new(
+            ParseErrorKind::PayloadSizeMismatch,
+            format!(
+                "Failed to decode message - declared size of {} bytes is too large",
+                body_len
+            ),
+        )
+        .at(control.len())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ProtocolDecoder, DEFAULT_MAX_CONTROL_LINE};
+    use crate::{ParseErrorKind, ProtocolMessage, ProtocolMessageRef, ServerError};
+
+    #[test]
+    fn decode_pipelined() {
+        let buf = b"+OK\r\nMSG FOO 1 5\r\nHello\r\nPING\r\nHMSG BAR 2 12 14\r\nNATS/1.0\r\n\r\nhi\r\n-ERR 'Stale Connection'\r\n";
+        let mut decoder = ProtocolDecoder::new();
+        let mut offset = 0;
+        let mut msgs = Vec::new();
+        while let Some((msg, consumed)) = decoder.decode(&buf[offset..]).unwrap() {
+            offset += consumed;
+            msgs.push(msg);
+        }
+        assert_eq!(offset, buf.len());
+        assert_eq!(msgs.len(), 5);
+        assert_eq!(msgs[0], ProtocolMessage::Ok);
+        match msgs[1] {
+            ProtocolMessage::Message(ref m) => assert_eq!(m.payload, &b"Hello"[..]),
+            _ => panic!("expected MSG"),
+        }
+        assert_eq!(msgs[2], ProtocolMessage::Ping);
+        match msgs[3] {
+            ProtocolMessage::HeaderMessage(ref m) => assert_eq!(m.payload, &b"hi"[..]),
+            _ => panic!("expected HMSG"),
+        }
+        assert_eq!(
+            msgs[4],
+            ProtocolMessage::Error(ServerError::from("Stale Connection"))
+        );
+    }
+
+    #[test]
+    fn decode_ref_pipelined() {
+        let buf =
+            b"MSG FOO 1 5\r\nHello\r\nHMSG BAR 2 12 14\r\nNATS/1.0\r\n\r\nhi\r\nPING\r\nMSG BA";
+        let mut decoder = ProtocolDecoder::new();
+        let mut offset = 0;
+        let mut msgs = Vec::new();
+        while let Some((msg, consumed)) = decoder.decode_ref(&buf[offset..]).unwrap() {
+            offset += consumed;
+            msgs.push(msg);
+        }
+        assert_eq!(offset, buf.len() - 6);
+        match msgs[0] {
+            ProtocolMessageRef::Message(m) => assert_eq!(m.payload, &b"Hello"[..]),
+            _ => panic!("expected MSG"),
+        }
+        match msgs[1] {
+            ProtocolMessageRef::HeaderMessage(m) => assert_eq!(m.subject, "BAR"),
+            _ => panic!("expected HMSG"),
+        }
+        assert_eq!(
+            msgs[2],
+            ProtocolMessageRef::Other(Box::new(ProtocolMessage::Ping))
+        );
+    }
+
+    #[test]
+    fn decode_every_split_point() {
+        let wire = b"MSG FOO 1 INBOX.1 12\r\nHello\r\nWorld\r\nPONG\r\n";
+        for split in 0..=wire.len() {
+            let mut decoder = ProtocolDecoder::new();
+            let mut buf = wire[..split].to_vec();
+            let mut msgs = Vec::new();
+            let mut fed_rest = false;
+            loop {
+                match decoder.decode(&buf).unwrap() {
+                    Some((msg, consumed)) => {
+                        buf.drain(..consumed);
+                        msgs.push(msg);
+                    }
+                    None if !fed_rest => {
+                        buf.extend_from_slice(&wire[split..]);
+                        fed_rest = true;
+                    }
+                    None => break,
+                }
+            }
+            assert_eq!(msgs.len(), 2, "split at {}", split);
+            assert_eq!(msgs[1], ProtocolMessage::Pong);
+            assert!(buf.is_empty());
+        }
+    }
+
+    #[test]
+    fn decode_malformed() {
+        let mut decoder = ProtocolDecoder::new();
+        assert!(decoder.decode(b"MSG FOO\r\n").is_err());
+        assert!(decoder.decode(b"MSG FOO 1 3\r\nabcd\r\n").is_err());
+        assert!(decoder.decode(b"WHAT\r\n").is_err());
+        assert!(decoder.decode(b"PIN").unwrap().is_none());
+    }
+
+    #[test]
+    fn decode_control_line_too_long() {
+        let mut decoder = ProtocolDecoder::with_max_control_line(16);
+        assert!(decoder.decode(b"MSG FOO.BAR 1 5").unwrap().is_none());
+        let err = decoder.decode(b"MSG FOO.BAR.BAZ.QUUX 1").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::ControlLineTooLong);
+        let err = decoder
+            .decode(b"MSG FOO.BAR.BAZ 1 5\r\nHello\r\n")
+            .unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::ControlLineTooLong);
+        assert!(decoder.decode(b"PUB FOO 5\r\nHello\r\n").unwrap().is_some());
+    }
+
+    #[test]
+    fn decode_payload_too_large() {
+        let mut decoder = ProtocolDecoder::new();
+        let err = decoder
+            .decode(b"MSG a 1 18446744073709551615\r\n")
+            .unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::PayloadTooLarge);
+
+        // rejected before the payload is buffered
+        let mut decoder = ProtocolDecoder::with_limits(DEFAULT_MAX_CONTROL_LINE, 8);
+        let err = decoder.decode(b"MSG a 1 9\r\n").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::PayloadTooLarge);
+        let err = decoder.decode(b"HMSG a 1 4 12\r\n").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::PayloadTooLarge);
+        assert!(decoder
+            .decode(b"MSG a 1 8\r\nHello NA\r\n")
+            .unwrap()
+            .is_some());
+
+        // without a limit the sum itself overflows
+        let mut decoder = ProtocolDecoder::with_limits(DEFAULT_MAX_CONTROL_LINE, usize::MAX);
+        let err = decoder
+            .decode(b"MSG a 1 18446744073709551615\r\n")
+            .unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::PayloadSizeMismatch);
+    }
+}
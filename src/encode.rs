@@ -0,0 +1,794 @@
+use crate::subject;
+use crate::{
+    ConnectionInformation, DeliveredMessage, HeaderDeliveredMessage, HeaderMap,
+    HeaderPublishMessage, ProtocolMessage, PublishMessage, ServerError, ServerInformation, Subject,
+    SubscribeMessage, UnsubscribeMessage,
+};
+#[cfg(feature = "bytes")]
+use bytes::BufMut;
+use std::io;
+use std::io::{IoSlice, Write};
+
+/// Encoding of a protocol message into its wire form in a caller-supplied buffer, allowing a
+/// client to batch many messages into one reusable write buffer without allocating for each.
+/// Payloads are written byte for byte, unlike with `Display`, which can only produce text.
+///
+/// ```rust
+/// extern crate nats_types;
+///
+/// use nats_types::{Encode, PublishMessage, UnsubscribeMessage};
+///
+/// let publish =
+///     PublishMessage::new("orders.new".parse().unwrap(), None, b"hello".to_vec()).unwrap();
+/// let unsub = UnsubscribeMessage::new(7, None);
+///
+/// let mut buf = Vec::with_capacity(publish.encoded_len() + unsub.encoded_len());
+/// publish.encode_into(&mut buf).unwrap();
+/// unsub.encode_into(&mut buf).unwrap();
+/// assert_eq!(buf, b"PUB orders.new 5\r\nhello\r\nUNSUB 7\r\n");
+/// ```
+pub trait Encode {
+    /// The exact number of bytes written by `encode_into`
+    fn encoded_len(&self) -> usize;
+
+    /// Writes the message to the given writer. Fails with `io::ErrorKind::InvalidInput` if the
+    /// message cannot be sent as it is, such as a `PUB` to a wildcard subject.
+    fn encode_into<W: Write + ?Sized>(&self, buf: &mut W) -> io::Result<()>;
+
+    /// Encodes the message into a new vector of exactly `encoded_len` bytes
+    fn encode(&self) -> io::Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(self.encoded_len());
+        self.encode_into(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Writes the message to the given buffer, such as a `BytesMut`. Available with the `bytes`
+    /// feature.
+    #[cfg(feature = "bytes")]
+    fn encode_into_buf<B: BufMut>(&self, buf: &mut B) -> io::Result<()> {
+        self.encode_into(&mut BufMut::writer(buf))
+    }
+}
+
+impl Encode for PublishMessage {
+    fn encoded_len(&self) -> usize {
+        pub_len(&self.subject, self.reply_to.as_deref(), self.payload.len())
+    }
+
+    fn encode_into<W: Write + ?Sized>(&self, buf: &mut W) -> io::Result<()> {
+        write_pub(buf, &self.subject, self.reply_to.as_deref(), &self.payload)
+    }
+}
+
+impl Encode for HeaderPublishMessage {
+    fn encoded_len(&self) -> usize {
+        hpub_len(
+            &self.subject,
+            self.reply_to.as_deref(),
+            &self.headers,
+            self.payload.len(),
+        )
+    }
+
+    fn encode_into<W: Write + ?Sized>(&self, buf: &mut W) -> io::Result<()> {
+        write_hpub(
+            buf,
+            &self.subject,
+            self.reply_to.as_deref(),
+            &self.headers,
+            &self.payload,
+        )
+    }
+}
+
+impl Encode for DeliveredMessage {
+    fn encoded_len(&self) -> usize {
+        msg_len(
+            &self.subject,
+            self.subscription_id,
+            self.reply_to.as_deref(),
+            self.payload.len(),
+        )
+    }
+
+    fn encode_into<W: Write + ?Sized>(&self, buf: &mut W) -> io::Result<()> {
+        write_msg(
+            buf,
+            &self.subject,
+            self.subscription_id,
+            self.reply_to.as_deref(),
+            &self.payload,
+        )
+    }
+}
+
+impl Encode for HeaderDeliveredMessage {
+    fn encoded_len(&self) -> usize {
+        hmsg_len(
+            &self.subject,
+            self.subscription_id,
+            self.reply_to.as_deref(),
+            &self.headers,
+            self.payload.len(),
+        )
+    }
+
+    fn encode_into<W: Write + ?Sized>(&self, buf: &mut W) -> io::Result<()> {
+        write_hmsg(
+            buf,
+            &self.subject,
+            self.subscription_id,
+            self.reply_to.as_deref(),
+            &self.headers,
+            &self.payload,
+        )
+    }
+}
+
+impl Encode for SubscribeMessage {
+    fn encoded_len(&self) -> usize {
+        sub_len(
+            &self.subject,
+            self.queue_group.as_deref(),
+            self.subscription_id,
+        )
+    }
+
+    fn encode_into<W: Write + ?Sized>(&self, buf: &mut W) -> io::Result<()> {
+        write_sub(
+            buf,
+            &self.subject,
+            self.queue_group.as_deref(),
+            self.subscription_id,
+        )
+    }
+}
+
+impl Encode for UnsubscribeMessage {
+    fn encoded_len(&self) -> usize {
+        unsub_len(self.subscription_id, self.max_messages)
+    }
+
+    fn encode_into<W: Write + ?Sized>(&self, buf: &mut W) -> io::Result<()> {
+        write_unsub(buf, self.subscription_id, self.max_messages)
+    }
+}
+
+impl Encode for ConnectionInformation {
+    fn encoded_len(&self) -> usize {
+        let mut counter = Counter(0);
+        // counting cannot fail, and serialization failures are reported by encode_into
+        let _ = self.encode_into(&mut counter);
+        counter.0
+    }
+
+    fn encode_into<W: Write + ?Sized>(&self, buf: &mut W) -> io::Result<()> {
+        buf.write_all(b"CONNECT ")?;
+        serde_json::to_writer(&mut *buf, self)?;
+        buf.write_all(b"\r\n")
+    }
+}
+
+impl Encode for ServerInformation {
+    fn encoded_len(&self) -> usize {
+        let mut counter = Counter(0);
+        // counting cannot fail, and serialization failures are reported by encode_into
+        let _ = self.encode_into(&mut counter);
+        counter.0
+    }
+
+    fn encode_into<W: Write + ?Sized>(&self, buf: &mut W) -> io::Result<()> {
+        buf.write_all(b"INFO ")?;
+        serde_json::to_writer(&mut *buf, self)?;
+        buf.write_all(b"\r\n")
+    }
+}
+
+impl Encode for ServerError {
+    fn encoded_len(&self) -> usize {
+        let mut counter = Counter(0);
+        let _ = self.encode_into(&mut counter);
+        counter.0
+    }
+
+    fn encode_into<W: Write + ?Sized>(&self, buf: &mut W) -> io::Result<()> {
+        write!(buf, "-ERR '{}'\r\n", self)
+    }
+}
+
+impl Encode for HeaderMap {
+    fn encoded_len(&self) -> usize {
+        let status = match self.status() {
+            Some(status) => {
+                1 + digits(usize::from(status)) + self.description().map_or(0, |d| d.len() + 1)
+            }
+            None => 0,
+        };
+        let entries: usize = self.iter().map(|(k, v)| k.len() + v.len() + 4).sum();
+        "NATS/1.0".len() + status + 2 + entries + 2
+    }
+
+    fn encode_into<W: Write + ?Sized>(&self, buf: &mut W) -> io::Result<()> {
+        write!(buf, "{}", self)
+    }
+}
+
+impl Encode for ProtocolMessage {
+    fn encoded_len(&self) -> usize {
+        match self {
+            ProtocolMessage::Unsubscribe(m) => m.encoded_len(),
+            ProtocolMessage::Subscribe(m) => m.encoded_len(),
+            ProtocolMessage::Publish(m) => m.encoded_len(),
+            ProtocolMessage::HeaderPublish(m) => m.encoded_len(),
+            ProtocolMessage::Message(m) => m.encoded_len(),
+            ProtocolMessage::HeaderMessage(m) => m.encoded_len(),
+            ProtocolMessage::Ping => b"PING\r\n".len(),
+            ProtocolMessage::Pong => b"PONG\r\n".len(),
+            ProtocolMessage::Ok => b"+OK\r\n".len(),
+            ProtocolMessage::Error(e) => e.encoded_len(),
+            ProtocolMessage::Info(si) => si.encoded_len(),
+            ProtocolMessage::Connect(ci) => ci.encoded_len(),
+        }
+    }
+
+    fn encode_into<W: Write + ?Sized>(&self, buf: &mut W) -> io::Result<()> {
+        match self {
+            ProtocolMessage::Unsubscribe(m) => m.encode_into(buf),
+            ProtocolMessage::Subscribe(m) => m.encode_into(buf),
+            ProtocolMessage::Publish(m) => m.encode_into(buf),
+            ProtocolMessage::HeaderPublish(m) => m.encode_into(buf),
+            ProtocolMessage::Message(m) => m.encode_into(buf),
+            ProtocolMessage::HeaderMessage(m) => m.encode_into(buf),
+            ProtocolMessage::Ping => buf.write_all(b"PING\r\n"),
+            ProtocolMessage::Pong => buf.write_all(b"PONG\r\n"),
+            ProtocolMessage::Ok => buf.write_all(b"+OK\r\n"),
+            ProtocolMessage::Error(e) => e.encode_into(buf),
+            ProtocolMessage::Info(si) => si.encode_into(buf),
+            ProtocolMessage::Connect(ci) => ci.encode_into(buf),
+        }
+    }
+}
+
+impl PublishMessage {
+    /// Encodes the message as the slices to pass to `write_vectored`, so that the payload is
+    /// written without being copied: the control line, formatted into `head` (which is cleared
+    /// first and can be reused between messages), the payload and the trailing CRLF. As with
+    /// any vectored write, the caller must handle partial writes, e.g. with
+    /// `IoSlice::advance_slices`.
+    ///
+    /// ```rust
+    /// extern crate nats_types;
+    ///
+    /// use nats_types::PublishMessage;
+    ///
+    /// let publish =
+    ///     PublishMessage::new("orders.new".parse().unwrap(), None, vec![0u8; 1 << 20]).unwrap();
+    /// let mut head = Vec::new();
+    /// let slices = publish.encode_vectored(&mut head).unwrap();
+    /// assert_eq!(&*slices[0], b"PUB orders.new 1048576\r\n");
+    /// assert_eq!(slices[1].as_ptr(), publish.payload.as_ptr());
+    /// assert_eq!(&*slices[2], b"\r\n");
+    /// ```
+    pub fn encode_vectored<'a>(&'a self, head: &'a mut Vec<u8>) -> io::Result<[IoSlice<'a>; 3]> {
+        head.clear();
+        write_pub_head(
+            head,
+            &self.subject,
+            self.reply_to.as_deref(),
+            self.payload.len(),
+        )?;
+        Ok([
+            IoSlice::new(head),
+            IoSlice::new(&self.payload),
+            IoSlice::new(b"\r\n"),
+        ])
+    }
+}
+
+impl HeaderPublishMessage {
+    /// Encodes the message as the slices to pass to `write_vectored`, so that the payload is
+    /// written without being copied: the control line and the serialized headers, both
+    /// formatted into `head` (which is cleared first and can be reused between messages), the
+    /// payload and the trailing CRLF. As with any vectored write, the caller must handle
+    /// partial writes.
+    pub fn encode_vectored<'a>(&'a self, head: &'a mut Vec<u8>) -> io::Result<[IoSlice<'a>; 4]> {
+        head.clear();
+        write_hpub_head(
+            head,
+            &self.subject,
+            self.reply_to.as_deref(),
+            &self.headers,
+            self.payload.len(),
+        )?;
+        let (control, headers) = head.split_at(head.len() - self.headers.encoded_len());
+        Ok([
+            IoSlice::new(control),
+            IoSlice::new(headers),
+            IoSlice::new(&self.payload),
+            IoSlice::new(b"\r\n"),
+        ])
+    }
+}
+
+// A writer that only counts the bytes written to it
+struct Counter(usize);
+
+impl Write for Counter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// The number of decimal digits in n
+fn digits(mut n: usize) -> usize {
+    let mut digits = 1;
+    while n >= 10 {
+        n /= 10;
+        digits += 1;
+    }
+    digits
+}
+
+// The length of an optional space-separated argument
+fn opt_len(arg: Option<&str>) -> usize {
+    arg.map_or(0, |a| a.len() + 1)
+}
+
+fn check_publishable(subject: &Subject, reply_to: Option<&str>) -> io::Result<()> {
+    subject::validate_publish(subject, reply_to)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+pub(crate) fn pub_len(subject: &Subject, reply_to: Option<&str>, payload_len: usize) -> usize {
+    "PUB ".len()
+        + subject.as_str().len()
+        + opt_len(reply_to)
+        + 1
+        + digits(payload_len)
+        + 2
+        + payload_len
+        + 2
+}
+
+pub(crate) fn write_pub<W: Write + ?Sized>(
+    buf: &mut W,
+    subject: &Subject,
+    reply_to: Option<&str>,
+    payload: &[u8],
+) -> io::Result<()> {
+    write_pub_head(buf, subject, reply_to, payload.len())?;
+    buf.write_all(payload)?;
+    buf.write_all(b"\r\n")
+}
+
+// Writes everything that precedes the payload of a PUB
+fn write_pub_head<W: Write + ?Sized>(
+    buf: &mut W,
+    subject: &Subject,
+    reply_to: Option<&str>,
+    payload_len: usize,
+) -> io::Result<()> {
+    check_publishable(subject, reply_to)?;
+    write!(buf, "PUB {}", subject)?;
+    if let Some(rt) = reply_to {
+        write!(buf, " {}", rt)?;
+    }
+    write!(buf, " {}\r\n", payload_len)
+}
+
+pub(crate) fn hpub_len(
+    subject: &Subject,
+    reply_to: Option<&str>,
+    headers: &HeaderMap,
+    payload_len: usize,
+) -> usize {
+    let header_len = headers.encoded_len();
+    "HPUB ".len()
+        + subject.as_str().len()
+        + opt_len(reply_to)
+        + 1
+        + digits(header_len)
+        + 1
+        + digits(header_len + payload_len)
+        + 2
+        + header_len
+        + payload_len
+        + 2
+}
+
+pub(crate) fn write_hpub<W: Write + ?Sized>(
+    buf: &mut W,
+    subject: &Subject,
+    reply_to: Option<&str>,
+    headers: &HeaderMap,
+    payload: &[u8],
+) -> io::Result<()> {
+    write_hpub_head(buf, subject, reply_to, headers, payload.len())?;
+    buf.write_all(payload)?;
+    buf.write_all(b"\r\n")
+}
+
+// Writes everything that precedes the payload of an HPUB, i.e. the control line and headers
+fn write_hpub_head<W: Write + ?Sized>(
+    buf: &mut W,
+    subject: &Subject,
+    reply_to: Option<&str>,
+    headers: &HeaderMap,
+    payload_len: usize,
+) -> io::Result<()> {
+    check_publishable(subject, reply_to)?;
+    let header_len = headers.encoded_len();
+    write!(buf, "HPUB {}", subject)?;
+    if let Some(rt) = reply_to {
+        write!(buf, " {}", rt)?;
+    }
+    write!(buf, " {} {}\r\n", header_len, header_len + payload_len)?;
+    headers.encode_into(buf)
+}
+
+pub(crate) fn msg_len(
+    subject: &Subject,
+    subscription_id: usize,
+    reply_to: Option<&str>,
+    payload_len: usize,
+) -> usize {
+    "MSG ".len()
+        + subject.as_str().len()
+        + 1
+        + digits(subscription_id)
+        + opt_len(reply_to)
+        + 1
+        + digits(payload_len)
+        + 2
+        + payload_len
+        + 2
+}
+
+pub(crate) fn write_msg<W: Write + ?Sized>(
+    buf: &mut W,
+    subject: &Subject,
+    subscription_id: usize,
+    reply_to: Option<&str>,
+    payload: &[u8],
+) -> io::Result<()> {
+    write!(buf, "MSG {} {}", subject, subscription_id)?;
+    if let Some(rt) = reply_to {
+        write!(buf, " {}", rt)?;
+    }
+    write!(buf, " {}\r\n", payload.len())?;
+    buf.write_all(payload)?;
+    buf.write_all(b"\r\n")
+}
+
+pub(crate) fn hmsg_len(
+    subject: &Subject,
+    subscription_id: usize,
+    reply_to: Option<&str>,
+    headers: &HeaderMap,
+    payload_len: usize,
+) -> usize {
+    let header_len = headers.encoded_len();
+    "HMSG ".len()
+        + subject.as_str().len()
+        + 1
+        + digits(subscription_id)
+        + opt_len(reply_to)
+        + 1
+        + digits(header_len)
+        + 1
+        + digits(header_len + payload_len)
+        + 2
+        + header_len
+        + payload_len
+        + 2
+}
+
+pub(crate) fn write_hmsg<W: Write + ?Sized>(
+    buf: &mut W,
+    subject: &Subject,
+    subscription_id: usize,
+    reply_to: Option<&str>,
+    headers: &HeaderMap,
+    payload: &[u8],
+) -> io::Result<()> {
+    let header_len = headers.encoded_len();
+    write!(buf, "HMSG {} {}", subject, subscription_id)?;
+    if let Some(rt) = reply_to {
+        write!(buf, " {}", rt)?;
+    }
+    write!(buf, " {} {}\r\n", header_len, header_len + payload.len())?;
+    headers.encode_into(buf)?;
+    buf.write_all(payload)?;
+    buf.write_all(b"\r\n")
+}
+
+pub(crate) fn sub_len(
+    subject: &Subject,
+    queue_group: Option<&str>,
+    subscription_id: usize,
+) -> usize {
+    "SUB ".len() + subject.as_str().len() + opt_len(queue_group) + 1 + digits(subscription_id) + 2
+}
+
+pub(crate) fn write_sub<W: Write + ?Sized>(
+    buf: &mut W,
+    subject: &Subject,
+    queue_group: Option<&str>,
+    subscription_id: usize,
+) -> io::Result<()> {
+    if let Some(qg) = queue_group {
+        subject::validate_queue_group(qg)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    }
+    write!(buf, "SUB {}", subject)?;
+    if let Some(qg) = queue_group {
+        write!(buf, " {}", qg)?;
+    }
+    write!(buf, " {}\r\n", subscription_id)
+}
+
+pub(crate) fn unsub_len(subscription_id: usize, max_messages: Option<usize>) -> usize {
+    "UNSUB ".len() + digits(subscription_id) + max_messages.map_or(0, |m| digits(m) + 1) + 2
+}
+
+pub(crate) fn write_unsub<W: Write + ?Sized>(
+    buf: &mut W,
+    subscription_id: usize,
+    max_messages: Option<usize>,
+) -> io::Result<()> {
+    write!(buf, "UNSUB {}", subscription_id)?;
+    if let Some(m) = max_messages {
+        write!(buf, " {}", m)?;
+    }
+    buf.write_all(b"\r\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{digits, Encode};
+    use crate::{
+        ConnectionInformation, DeliveredMessage, HeaderDeliveredMessage, HeaderMap,
+        HeaderPublishMessage, ParseErrorKind, Payload, ProtocolMessage, PublishMessage,
+        ServerError, SubscribeMessage, UnsubscribeMessage,
+    };
+    use std::io;
+    use std::io::{IoSlice, Write};
+
+    // a writer that accepts at most 7 bytes per call, forcing partial writes
+    struct Trickle(Vec<u8>);
+
+    impl Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let n = buf.len().min(7);
+            self.0.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // the bytes written by passing the slices to Trickle until all are written
+    fn write_all_vectored(mut slices: &mut [IoSlice]) -> Vec<u8> {
+        let mut out = Trickle(Vec::new());
+        while !slices.is_empty() {
+            let n = out.write_vectored(slices).unwrap();
+            IoSlice::advance_slices(&mut slices, n);
+        }
+        out.0
+    }
+
+    fn messages() -> Vec<ProtocolMessage> {
+        let mut headers = HeaderMap::with_status(503, Some("No Responders".to_string())).unwrap();
+        headers.append("Nats-Msg-Id", "42").unwrap();
+        vec![
+            ProtocolMessage::Publish(
+                PublishMessage::new(
+                    "orders.new".parse().unwrap(),
+                    Some("INBOX.1".to_string()),
+                    vec![0u8; 1234],
+                )
+                .unwrap(),
+            ),
+            ProtocolMessage::HeaderPublish(
+                HeaderPublishMessage::new(
+                    "orders.new".parse().unwrap(),
+                    None,
+                    headers.clone(),
+                    b"hi".to_vec(),
+                )
+                .unwrap(),
+            ),
+            ProtocolMessage::Message(DeliveredMessage::new(
+                "orders.new".parse().unwrap(),
+                123_456,
+                None,
+                Vec::new(),
+            )),
+            ProtocolMessage::HeaderMessage(HeaderDeliveredMessage::new(
+                "orders.new".parse().unwrap(),
+                9,
+                Some("INBOX.2".to_string()),
+                headers,
+                b"hello".to_vec(),
+            )),
+            ProtocolMessage::Subscribe(
+                SubscribeMessage::new("orders.*".parse().unwrap(), Some("workers".to_string()), 10)
+                    .unwrap(),
+            ),
+            ProtocolMessage::Unsubscribe(UnsubscribeMessage::new(10, Some(100))),
+            ProtocolMessage::Ping,
+            ProtocolMessage::Ok,
+            ProtocolMessage::Error(ServerError::from("Stale Connection")),
+            ProtocolMessage::Connect(ConnectionInformation::builder().name("encode").build()),
+        ]
+    }
+
+    #[test]
+    fn encoding_matches_display() {
+        for msg in messages() {
+            let encoded = msg.encode().unwrap();
+            assert_eq!(encoded.len(), msg.encoded_len(), "{}", msg);
+            let expected = match msg {
+                // the Display of -ERR leaves out the line terminator
+                ProtocolMessage::Error(_) => format!("{}\r\n", msg),
+                _ => msg.to_string(),
+            };
+            assert_eq!(String::from_utf8(encoded).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn binary_payload() {
+        let payload = vec![0xff, 0x00, 0xfe, b'\r', b'\n', 0x80];
+        let mut headers = HeaderMap::new();
+        headers
+            .append("Content-Type", "application/octet-stream")
+            .unwrap();
+        let msgs = vec![
+            ProtocolMessage::Publish(
+                PublishMessage::new("blobs".parse().unwrap(), None, payload.clone()).unwrap(),
+            ),
+            ProtocolMessage::HeaderPublish(
+                HeaderPublishMessage::new("blobs".parse().unwrap(), None, headers, payload.clone())
+                    .unwrap(),
+            ),
+        ];
+        for msg in msgs {
+            let encoded = msg.encode().unwrap();
+            assert!(encoded.windows(payload.len()).any(|w| w == &payload[..]));
+            assert_eq!(ProtocolMessage::parse(&encoded).unwrap(), msg);
+            // formatting can't carry the payload
+            assert!(msg.to_string().contains("<<BAD PAYLOAD>>"));
+        }
+    }
+
+    #[test]
+    fn batch_into_reused_buffer() {
+        let msgs = messages();
+        let len: usize = msgs.iter().map(Encode::encoded_len).sum();
+        let mut buf = Vec::with_capacity(len);
+        for _ in 0..2 {
+            buf.clear();
+            for msg in &msgs {
+                msg.encode_into(&mut buf).unwrap();
+            }
+            assert_eq!(buf.len(), len);
+            assert_eq!(buf.capacity(), len);
+        }
+    }
+
+    #[test]
+    fn encode_rejects_wildcard_publish() {
+        let err = PublishMessage::new("orders.*".parse().unwrap(), None, Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::InvalidSubject);
+        let headers = HeaderMap::new();
+        assert!(
+            HeaderPublishMessage::new(">".parse().unwrap(), None, headers, Vec::new()).is_err()
+        );
+
+        // the fields are public, so encoding guards against wildcards too
+        let msg = PublishMessage {
+            subject: "orders.*".parse().unwrap(),
+            reply_to: None,
+            payload_size: 0,
+            payload: Payload::new(),
+        };
+        let err = msg.encode_into(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn encode_rejects_control_line_injection() {
+        let subject: crate::Subject = "a.b".parse().unwrap();
+        for reply in &["r\r\nUNSUB 1", "r s", "_INBOX.*", ""] {
+            let reply = Some(reply.to_string());
+            let err = PublishMessage::new(subject.clone(), reply.clone(), Vec::new()).unwrap_err();
+            assert_eq!(err.kind(), ParseErrorKind::InvalidSubject);
+            let headers = HeaderMap::new();
+            assert!(
+                HeaderPublishMessage::new(subject.clone(), reply, headers, Vec::new()).is_err()
+            );
+        }
+        for queue_group in &["q\r\nUNSUB 1", "q w", ""] {
+            let queue_group = Some(queue_group.to_string());
+            let err = SubscribeMessage::new(subject.clone(), queue_group, 1).unwrap_err();
+            assert_eq!(err.kind(), ParseErrorKind::InvalidSubject);
+        }
+
+        // the fields are public, so encoding checks them too, before writing anything
+        let mut buf = Vec::new();
+        let msg = PublishMessage {
+            subject: subject.clone(),
+            reply_to: Some("r\r\nUNSUB 1".to_string()),
+            payload_size: 2,
+            payload: b"hi"[..].into(),
+        };
+        let err = msg.encode_into(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let msg = SubscribeMessage {
+            subject,
+            queue_group: Some("q\r\nUNSUB 1".to_string()),
+            subscription_id: 1,
+        };
+        let err = msg.encode_into(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(buf.is_empty());
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn encode_into_bytes_mut() {
+        let mut buf = bytes::BytesMut::new();
+        let mut expected = Vec::new();
+        for msg in messages() {
+            msg.encode_into_buf(&mut buf).unwrap();
+            msg.encode_into(&mut expected).unwrap();
+        }
+        assert_eq!(&buf[..], &expected[..]);
+    }
+
+    #[test]
+    fn vectored_publish() {
+        let msgs = messages();
+        let mut head = Vec::new();
+        match &msgs[0] {
+            ProtocolMessage::Publish(m) => {
+                let mut slices = m.encode_vectored(&mut head).unwrap();
+                assert_eq!(write_all_vectored(&mut slices), m.encode().unwrap());
+            }
+            _ => unreachable!(),
+        }
+        match &msgs[1] {
+            ProtocolMessage::HeaderPublish(m) => {
+                let mut slices = m.encode_vectored(&mut head).unwrap();
+                assert_eq!(&*slices[0], b"HPUB orders.new 47 49\r\n");
+                assert_eq!(&*slices[1], &m.headers.encode().unwrap()[..]);
+                assert_eq!(write_all_vectored(&mut slices), m.encode().unwrap());
+            }
+            _ => unreachable!(),
+        }
+
+        let msg = PublishMessage {
+            subject: "orders.*".parse().unwrap(),
+            reply_to: None,
+            payload_size: 0,
+            payload: Payload::new(),
+        };
+        assert!(msg.encode_vectored(&mut head).is_err());
+    }
+
+    #[test]
+    fn digit_counts() {
+        assert_eq!(digits(0), 1);
+        assert_eq!(digits(9), 1);
+        assert_eq!(digits(10), 2);
+        assert_eq!(digits(usize::MAX), usize::MAX.to_string().len());
+    }
+}
@@ -0,0 +1,121 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+/// The category of failure reported by a `NatsParseError`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ParseErrorKind {
+    /// The input does not begin with a known protocol verb
+    UnknownOperation,
+    /// The control line could not be parsed, e.g. a missing field or a non-numeric size
+    MalformedControlLine,
+    /// The JSON body of an `INFO` or `CONNECT` message could not be deserialized
+    InvalidJson,
+    /// The payload is not followed by a CRLF at the position given by its declared size
+    PayloadSizeMismatch,
+    /// The `NATS/1.0` header block of an `HPUB` or `HMSG` message could not be parsed
+    MalformedHeaders,
+    /// The input ends before the message is complete; more data is needed
+    Incomplete,
+    /// The control line exceeds the maximum length accepted by the decoder
+    ControlLineTooLong,
+    /// The declared size of a message body exceeds the maximum accepted by the decoder
+    PayloadTooLarge,
+    /// A subject is empty, contains whitespace or empty tokens, or uses a wildcard where one
+    /// is not allowed, or a queue group is empty or contains whitespace
+    InvalidSubject,
+    /// A reply subject is missing or is not a well formed JetStream `$JS.ACK` subject
+    InvalidAckSubject,
+    /// A configuration value is outside the range accepted by the server
+    InvalidConfig,
+}
+
+/// Indicates an error occurred during parsing of a NATS protocol message. The `kind` can be
+/// matched on to decide how to react, and the `offset` gives the position in the input at which
+/// the problem was detected.
+#[derive(Debug)]
+pub struct NatsParseError {
+    kind: ParseErrorKind,
+    offset: usize,
+    verb: Option<String>,
+    msg: String,
+    source: Option<serde_json::Error>,
+}
+
+impl NatsParseError {
+    pub(crate) fn new<S: Into<String>>(kind: ParseErrorKind, msg: S) -> NatsParseError {
+        NatsParseError {
+            kind,
+            offset: 0,
+            verb: None,
+            msg: msg.into(),
+            source: None,
+        }
+    }
+
+    pub(crate) fn json(verb: &str, offset: usize, err: serde_json::Error) -> NatsParseError {
+        NatsParseError::new(
+            ParseErrorKind::InvalidJson,
+            format!("Failed to parse {} JSON: {}", verb, err),
+        )
+        .with_verb(verb)
+        .at(offset)
+        .with_source(err)
+    }
+
+    pub(crate) fn with_verb<S: Into<String>>(mut self, verb: S) -> NatsParseError {
+        self.verb = Some(verb.into());
+        self
+    }
+
+    pub(crate) fn at(mut self, offset: usize) -> NatsParseError {
+        self.offset = offset;
+        self
+    }
+
+    // shifts the offset when an error from a nested parse is reported against the whole message
+    pub(crate) fn offset_by(mut self, start: usize) -> NatsParseError {
+        self.offset += start;
+        self
+    }
+
+    pub(crate) fn with_source(mut self, source: serde_json::Error) -> NatsParseError {
+        self.source = Some(source);
+        self
+    }
+
+    /// The category of this error
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind
+    }
+
+    /// The byte offset into the input at which the problem was detected
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The protocol verb (e.g. `MSG` or `INFO`) of the message being parsed. For an unknown
+    /// operation this is the offending leading token of the input.
+    pub fn verb(&self) -> Option<&str> {
+        self.verb.as_deref()
+    }
+
+    /// Indicates whether the input was a valid prefix of a message that ended too soon
+    pub fn is_incomplete(&self) -> bool {
+        self.kind == ParseErrorKind::Incomplete
+    }
+}
+
+impl Error for NatsParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_ref().map(|e| e as &(dyn Error + 'static))
+    }
+}
+
+impl Display for NatsParseError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
@@ -0,0 +1,430 @@
+use crate::parser;
+use crate::{NatsParseError, ParseErrorKind};
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+/// Status code sent by the server as an idle heartbeat or flow control request
+pub const STATUS_IDLE_HEARTBEAT: u16 = 100;
+/// Status code sent by the server when a request could not be satisfied (e.g. no messages)
+pub const STATUS_NOT_FOUND: u16 = 404;
+/// Status code sent by the server when a pull request expired
+pub const STATUS_REQUEST_TIMEOUT: u16 = 408;
+/// Status code sent by the server when a request was published to a subject with no subscribers
+pub const STATUS_NO_RESPONDERS: u16 = 503;
+
+/// A multi-value map representing the header block carried by `HPUB` and `HMSG` messages.
+/// The block has the following format:
+/// ```text
+/// NATS/1.0 [status] [description]\r\n[key]: [value]\r\n...\r\n\r\n
+/// ```
+/// Keys are compared case-sensitively and keep the case they were inserted with. Entries are
+/// kept in insertion order and a key may appear more than once. Keys and values may not
+/// contain CR or LF characters, and keys may not be empty or contain `:`.
+///
+/// ```rust
+/// extern crate nats_types;
+///
+/// use nats_types::HeaderMap;
+///
+/// let mut headers = HeaderMap::new();
+/// headers.append("Nats-Msg-Id", "42").unwrap();
+/// headers.append("X-Trace", "a").unwrap();
+/// headers.append("X-Trace", "b").unwrap();
+/// assert!(headers.append("X-Trace", "c\r\nNats-Rollup: all").is_err());
+///
+/// assert_eq!(headers.get("Nats-Msg-Id"), Some("42"));
+/// assert_eq!(headers.get_all("X-Trace").collect::<Vec<_>>(), vec!["a", "b"]);
+/// assert_eq!(
+///     format!("{}", headers),
+///     "NATS/1.0\r\nNats-Msg-Id: 42\r\nX-Trace: a\r\nX-Trace: b\r\n\r\n"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct HeaderMap {
+    status: Option<u16>,
+    description: Option<String>,
+    entries: Vec<(String, String)>,
+}
+
+impl HeaderMap {
+    /// Creates an empty header map with no inline status
+    pub fn new() -> HeaderMap {
+        HeaderMap::default()
+    }
+
+    /// Creates an empty header map carrying an inline status code and optional description,
+    /// as found in server-generated messages such as `NATS/1.0 503`. Fails if the status is not
+    /// three digits or the description contains CR or LF characters.
+    pub fn with_status(
+        status: u16,
+        description: Option<String>,
+    ) -> Result<HeaderMap, NatsParseError> {
+        if !(100..=999).contains(&status) {
+            return Err(NatsParseError::new(
+                ParseErrorKind::MalformedHeaders,
+                format!("Invalid status {} - statuses are three digits", status),
+            ));
+        }
+        if let Some(desc) = description.as_deref().filter(|d| breaks_line(d)) {
+            return Err(NatsParseError::new(
+                ParseErrorKind::MalformedHeaders,
+                format!("Invalid status description {:?}", desc),
+            ));
+        }
+        Ok(HeaderMap {
+            status: Some(status),
+            description,
+            entries: Vec::new(),
+        })
+    }
+
+    /// The inline status code from the version line, if any
+    pub fn status(&self) -> Option<u16> {
+        self.status
+    }
+
+    /// The inline status description from the version line, if any
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Indicates whether this header block is a `503` no responders notification
+    pub fn is_no_responders(&self) -> bool {
+        self.status == Some(STATUS_NO_RESPONDERS)
+    }
+
+    /// Adds a value for the given key, keeping any values already present. Fails if the key or
+    /// value could not be written to the header block as given.
+    pub fn append<K: Into<String>, V: Into<String>>(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> Result<(), NatsParseError> {
+        let (key, value) = checked_entry(key.into(), value.into())?;
+        self.entries.push((key, value));
+        Ok(())
+    }
+
+    /// Sets the value for the given key, replacing all values already present. If the key
+    /// already exists, the new value takes the position of its first occurrence. Fails if the
+    /// key or value could not be written to the header block as given.
+    pub fn insert<K: Into<String>, V: Into<String>>(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> Result<(), NatsParseError> {
+        let (key, value) = checked_entry(key.into(), value.into())?;
+        match self.entries.iter().position(|(k, _)| *k == key) {
+            Some(idx) => {
+                let mut i = 0;
+                self.entries.retain(|(k, _)| {
+                    let keep = i <= idx || *k != key;
+                    i += 1;
+                    keep
+                });
+                self.entries[idx].1 = value;
+            }
+            None => self.entries.push((key, value)),
+        }
+        Ok(())
+    }
+
+    /// Removes every value for the given key, returning them in insertion order
+    pub fn remove(&mut self, key: &str) -> Vec<String> {
+        let mut removed = Vec::new();
+        let mut kept = Vec::with_capacity(self.entries.len());
+        for (k, v) in self.entries.drain(..) {
+            if k == key {
+                removed.push(v);
+            } else {
+                kept.push((k, v));
+            }
+        }
+        self.entries = kept;
+        removed
+    }
+
+    /// Returns the first value for the given key
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns every value for the given key in insertion order
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Indicates whether at least one value exists for the given key
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.entries.iter().any(|(k, _)| k == key)
+    }
+
+    /// Iterates over all key-value pairs in insertion order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// The number of key-value pairs, counting repeated keys once per value
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Indicates whether the map has no key-value pairs. An empty map may still carry a status.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Conversion into the serialized header block, including the trailing empty line
+    pub fn as_vec(&self) -> Vec<u8> {
+        self.to_string().into_bytes()
+    }
+
+    /// Parses a header block from raw bytes as received in an `HPUB` or `HMSG` message
+    pub fn from_bytes(bytes: &[u8]) -> Result<HeaderMap, NatsParseError> {
+        match std::str::from_utf8(bytes) {
+            Ok(s) => HeaderMap::from_str(s),
+            Err(e) => Err(NatsParseError::new(
+                ParseErrorKind::MalformedHeaders,
+                "Failed to parse headers - header block is not valid UTF-8",
+            )
+            .at(e.valid_up_to())),
+        }
+    }
+}
+
+// Rejects keys and values that would break out of their line of the header block, or in the
+// case of keys, be split differently when parsed
+fn breaks_line(s: &str) -> bool {
+    s.contains(['\r', '\n'])
+}
+
+fn checked_entry(key: String, value: String) -> Result<(String, String), NatsParseError> {
+    if key.is_empty() || key.contains(':') || breaks_line(&key) {
+        Err(NatsParseError::new(
+            ParseErrorKind::MalformedHeaders,
+            format!("Invalid header key {:?}", key),
+        ))
+    } else if breaks_line(&value) {
+        Err(NatsParseError::new(
+            ParseErrorKind::MalformedHeaders,
+            format!("Invalid value {:?} for header {}", value, key),
+        ))
+    } else {
+        Ok((key, value))
+    }
+}
+
+impl Display for HeaderMap {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "NATS/1.0")?;
+        if let Some(status) = self.status {
+            write!(f, " {}", status)?;
+            if let Some(ref desc) = self.description {
+                write!(f, " {}", desc)?;
+            }
+        }
+        write!(f, "\r\n")?;
+        for (k, v) in &self.entries {
+            write!(f, "{}: {}\r\n", k, v)?;
+        }
+        write!(f, "\r\n")
+    }
+}
+
+impl FromStr for HeaderMap {
+    type Err = NatsParseError;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        let (version_line, rest) = split_block(s)?;
+        let version = parser::parse_header_version_line(version_line.as_bytes())?;
+
+        let mut map = HeaderMap {
+            status: version.status,
+            description: version.description,
+            entries: Vec::new(),
+        };
+        for entry in entries(rest, version_line.len() + 2) {
+            let (k, v) = entry?;
+            map.entries.push((k.to_string(), v.to_string()));
+        }
+        Ok(map)
+    }
+}
+
+// Splits a header block into its version line and the `key: value` lines that follow it,
+// requiring the block to end with the empty line that terminates it
+fn split_block(s: &str) -> Result<(&str, &str), NatsParseError> {
+    let body = s.strip_suffix("\r\n\r\n").ok_or_else(|| {
+        NatsParseError::new(
+            ParseErrorKind::MalformedHeaders,
+            "Failed to parse headers - header block is not terminated by an empty line",
+        )
+        .at(s.len())
+    })?;
+    Ok(body.split_once("\r\n").unwrap_or((body, "")))
+}
+
+// Iterates over the `key: value` lines following the version line of a header block, with the
+// terminating empty line removed. `offset` is the position of the first line within the block.
+fn entries(
+    lines: &str,
+    mut offset: usize,
+) -> impl Iterator<Item = Result<(&str, &str), NatsParseError>> {
+    Some(lines)
+        .filter(|l| !l.is_empty())
+        .into_iter()
+        .flat_map(|l| l.split("\r\n"))
+        .map(move |line| {
+            let entry = match line.find(':') {
+                Some(idx) if idx > 0 => Ok((
+                    &line[..idx],
+                    line[idx + 1..].trim_start_matches([' ', '\t']),
+                )),
+                _ => Err(NatsParseError::new(
+                    ParseErrorKind::MalformedHeaders,
+                    format!("Failed to parse headers - malformed header line '{}'", line),
+                )
+                .at(offset)),
+            };
+            offset += line.len() + 2;
+            entry
+        })
+}
+
+// Checks that a raw header block is well formed without building a `HeaderMap`
+pub(crate) fn validate(bytes: &[u8]) -> Result<&str, NatsParseError> {
+    let s = std::str::from_utf8(bytes).map_err(|e| {
+        NatsParseError::new(
+            ParseErrorKind::MalformedHeaders,
+            "Failed to parse headers - header block is not valid UTF-8",
+        )
+        .at(e.valid_up_to())
+    })?;
+    let (version_line, rest) = split_block(s)?;
+    parser::parse_header_version_line(version_line.as_bytes())?;
+    for entry in entries(rest, version_line.len() + 2) {
+        entry?;
+    }
+    Ok(s)
+}
+
+// Looks up the first value of a key in a header block that has already been validated
+pub(crate) fn get_raw<'a>(block: &'a str, key: &str) -> Option<&'a str> {
+    let rest = split_block(block).map_or("", |(_, rest)| rest);
+    entries(rest, 0)
+        .filter_map(Result::ok)
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| v)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{HeaderMap, STATUS_IDLE_HEARTBEAT};
+    use crate::ParseErrorKind;
+    use std::str::FromStr;
+
+    #[test]
+    fn header_roundtrip() {
+        let raw = "NATS/1.0\r\nNats-Msg-Id: 1\r\nX-Multi: a\r\nx-multi: c\r\nX-Multi: b\r\n\r\n";
+        let headers = HeaderMap::from_str(raw).unwrap();
+        assert_eq!(headers.len(), 4);
+        assert_eq!(headers.status(), None);
+        assert_eq!(headers.get("Nats-Msg-Id"), Some("1"));
+        assert_eq!(
+            headers.get_all("X-Multi").collect::<Vec<_>>(),
+            vec!["a", "b"]
+        );
+        assert_eq!(headers.get("x-multi"), Some("c"));
+        assert_eq!(format!("{}", headers), raw);
+        assert_eq!(headers.as_vec(), raw.as_bytes());
+    }
+
+    #[test]
+    fn header_inline_status() {
+        let raw =
+            "NATS/1.0 100 Idle Heartbeat\r\nNats-Last-Consumer: 0\r\nNats-Last-Stream: 0\r\n\r\n";
+        let headers = HeaderMap::from_bytes(raw.as_bytes()).unwrap();
+        assert_eq!(headers.status(), Some(STATUS_IDLE_HEARTBEAT));
+        assert_eq!(headers.description(), Some("Idle Heartbeat"));
+        assert_eq!(headers.get("Nats-Last-Stream"), Some("0"));
+        assert_eq!(format!("{}", headers), raw);
+
+        let headers = HeaderMap::from_str("NATS/1.0 503\r\n\r\n").unwrap();
+        assert!(headers.is_no_responders());
+        assert_eq!(headers.description(), None);
+        assert!(headers.is_empty());
+
+        // statuses are exactly three digits rather than truncated to fit
+        for raw in &[
+            "NATS/1.0 70000\r\n\r\n",
+            "NATS/1.0 50\r\n\r\n",
+            "NATS/1.0 0503\r\n\r\n",
+        ] {
+            let err = HeaderMap::from_str(raw).unwrap_err();
+            assert_eq!(err.kind(), ParseErrorKind::MalformedHeaders, "{:?}", raw);
+        }
+        let hmsg = crate::ProtocolMessage::parse(b"HMSG a 1 18 18\r\nNATS/1.0 70000\r\n\r\n\r\n");
+        assert_eq!(hmsg.unwrap_err().kind(), ParseErrorKind::MalformedHeaders);
+        assert!(HeaderMap::with_status(70, None).is_err());
+        assert!(HeaderMap::with_status(1000, None).is_err());
+    }
+
+    #[test]
+    fn header_insert_replaces() {
+        let mut headers = HeaderMap::new();
+        for (k, v) in &[("A", "1"), ("B", "2"), ("A", "3")] {
+            headers.append(*k, *v).unwrap();
+        }
+        headers.insert("A", "4").unwrap();
+        assert_eq!(
+            headers.iter().collect::<Vec<_>>(),
+            vec![("A", "4"), ("B", "2")]
+        );
+        headers.insert("C", "5").unwrap();
+        assert_eq!(headers.remove("B"), vec!["2".to_string()]);
+        assert!(!headers.contains_key("B"));
+        assert_eq!(format!("{}", headers), "NATS/1.0\r\nA: 4\r\nC: 5\r\n\r\n");
+    }
+
+    #[test]
+    fn header_malformed() {
+        assert!(HeaderMap::from_str("HTTP/1.1\r\n\r\n").is_err());
+        assert!(HeaderMap::from_str("NATS/1.0\r\nNoColon\r\n\r\n").is_err());
+        assert!(HeaderMap::from_str("NATS/1.0\r\n: empty key\r\n\r\n").is_err());
+        assert!(HeaderMap::from_bytes(b"NATS/1.0\r\nA: \xff\r\n\r\n").is_err());
+        assert!(HeaderMap::from_str("NATS/1.0\r\nA: 1\r\n").is_err());
+        assert!(HeaderMap::from_str("NATS/1.0\r\n").is_err());
+        assert!(HeaderMap::from_str("NATS/1.0\r\nA: 1\r\n\r\nB: 2\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn header_injection_rejected() {
+        let mut headers = HeaderMap::new();
+        for (k, v) in &[
+            ("Nats-Msg-Id", "x\r\nNats-Rollup: all"),
+            ("Nats-Msg-Id", "x\n"),
+            ("X\rY", "1"),
+            ("X: Y", "1"),
+            ("", "1"),
+        ] {
+            let err = headers.append(*k, *v).unwrap_err();
+            assert_eq!(err.kind(), ParseErrorKind::MalformedHeaders);
+            assert!(headers.insert(*k, *v).is_err());
+        }
+        assert!(headers.is_empty());
+        headers.append("X-Empty", "").unwrap();
+        assert_eq!(headers.to_string(), "NATS/1.0\r\nX-Empty: \r\n\r\n");
+        for desc in &["x\r\nNats-Rollup: all", "x\n"] {
+            let err = HeaderMap::with_status(503, Some(desc.to_string())).unwrap_err();
+            assert_eq!(err.kind(), ParseErrorKind::MalformedHeaders);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use crate::nuid::{next_nuid, DIGITS};
+use crate::{NatsParseError, Subject};
+
+/// The prefix of inbox subjects used by NATS clients by default
+pub const INBOX_PREFIX: &str = "_INBOX";
+
+/// Creates a unique inbox subject of the form `_INBOX.<nuid>`, suitable for the `reply_to` of a
+/// request that expects a single subscriber of its own
+pub fn new_inbox() -> String {
+    format!("{}.{}", INBOX_PREFIX, next_nuid())
+}
+
+/// A multiplexed response inbox, allowing many requests to share a single subscription. The
+/// inbox subscribes to `<prefix>.<nuid>.*` once, and each request is given a reply subject
+/// ending in its own token so that responses can be routed back to it.
+///
+/// ```rust
+/// extern crate nats_types;
+///
+/// use nats_types::ResponseInbox;
+///
+/// let mut inbox = ResponseInbox::new();
+/// let sub = inbox.subscription_subject();
+/// let (reply_to, token) = inbox.next_reply();
+///
+/// assert!(sub.matches(&reply_to.parse().unwrap()));
+/// assert_eq!(inbox.token_of(&reply_to), Some(token.as_str()));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ResponseInbox {
+    // includes the trailing '.'
+    prefix: String,
+    next_token: u64,
+}
+
+impl Default for ResponseInbox {
+    fn default() -> Self {
+        ResponseInbox::new()
+    }
+}
+
+impl ResponseInbox {
+    /// Creates a response inbox under `_INBOX`
+    pub fn new() -> ResponseInbox {
+        ResponseInbox {
+            prefix: format!("{}.{}.", INBOX_PREFIX, next_nuid()),
+            next_token: 0,
+        }
+    }
+
+    /// Creates a response inbox under a custom prefix, such as one permitted by the user's
+    /// subscribe permissions. The prefix must be a subject without wildcards.
+    pub fn with_prefix(prefix: &str) -> Result<ResponseInbox, NatsParseError> {
+        let prefix = Subject::literal(prefix)?;
+        Ok(ResponseInbox {
+            prefix: format!("{}.{}.", prefix, next_nuid()),
+            next_token: 0,
+        })
+    }
+
+    /// The wildcard subject to subscribe to in order to receive every response
+    pub fn subscription_subject(&self) -> Subject {
+        Subject::new(format!("{}*", self.prefix)).expect("inbox prefix is a valid subject")
+    }
+
+    /// Generates the reply subject for a new request, returning it along with its token
+    pub fn next_reply(&mut self) -> (String, String) {
+        let token = base62(self.next_token);
+        self.next_token += 1;
+        (format!("{}{}", self.prefix, token), token)
+    }
+
+    /// Extracts the request token from the subject of a response delivered to this inbox
+    pub fn token_of<'a>(&self, subject: &'a str) -> Option<&'a str> {
+        subject
+            .strip_prefix(self.prefix.as_str())
+            .filter(|t| !t.is_empty() && !t.contains('.'))
+    }
+}
+
+fn base62(mut n: u64) -> String {
+    let mut out = Vec::new();
+    loop {
+        out.push(DIGITS[(n % DIGITS.len() as u64) as usize]);
+        n /= DIGITS.len() as u64;
+        if n == 0 {
+            break;
+        }
+    }
+    out.iter().rev().map(|c| *c as char).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{base62, new_inbox, ResponseInbox};
+    use crate::{PublishMessage, Subject};
+
+    #[test]
+    fn inbox_usable_as_reply_to() {
+        let inbox = new_inbox();
+        assert!(inbox.starts_with("_INBOX."));
+        assert_eq!(inbox.len(), 7 + 22);
+        assert_ne!(inbox, new_inbox());
+
+        let publish =
+            PublishMessage::new("svc".parse().unwrap(), Some(inbox.clone()), b"hi".to_vec())
+                .unwrap();
+        let out = format!("{}", publish);
+        assert_eq!(PublishMessage::parse(out.as_bytes()).unwrap(), publish);
+        assert!(Subject::literal(inbox).is_ok());
+    }
+
+    #[test]
+    fn response_inbox_tokens() {
+        let mut inbox = ResponseInbox::with_prefix("_MY.INBOX").unwrap();
+        let sub = inbox.subscription_subject();
+        assert!(sub.as_str().starts_with("_MY.INBOX."));
+        assert!(sub.as_str().ends_with(".*"));
+
+        let replies: Vec<(String, String)> = (0..100).map(|_| inbox.next_reply()).collect();
+        for (reply, token) in &replies {
+            assert!(sub.matches(&Subject::literal(reply.as_str()).unwrap()));
+            assert_eq!(inbox.token_of(reply), Some(token.as_str()));
+        }
+        assert_ne!(replies[0].1, replies[99].1);
+        assert_eq!(inbox.token_of("_INBOX.other.1"), None);
+        assert_eq!(inbox.token_of(&format!("{}.x", replies[0].0)), None);
+        assert!(ResponseInbox::with_prefix("_MY.*").is_err());
+    }
+
+    #[test]
+    fn base62_tokens() {
+        assert_eq!(base62(0), "0");
+        assert_eq!(base62(61), "z");
+        assert_eq!(base62(62), "10");
+    }
+}
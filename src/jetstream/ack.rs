@@ -0,0 +1,295 @@
+use super::PullRequest;
+use crate::{
+    DeliveredMessage, HeaderDeliveredMessage, NatsParseError, ParseErrorKind, PublishMessage,
+    Subject,
+};
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The prefix of the reply subjects of messages delivered by JetStream consumers
+pub const ACK_PREFIX: &str = "$JS.ACK";
+
+/// The metadata JetStream encodes in the reply subject of a message delivered by a consumer,
+/// in either the original layout
+/// ```text
+/// $JS.ACK.<stream>.<consumer>.<delivered>.<sseq>.<cseq>.<ts>.<pending>
+/// ```
+/// or the layout used since domains were introduced, which may be followed by further tokens
+/// ```text
+/// $JS.ACK.<domain>.<acchash>.<stream>.<consumer>.<delivered>.<sseq>.<cseq>.<ts>.<pending>
+/// ```
+///
+/// ```rust
+/// extern crate nats_types;
+///
+/// use nats_types::JsMessageMetadata;
+///
+/// let meta: JsMessageMetadata = "$JS.ACK.ORDERS.EU.2.48.7.1704164645000000000.10"
+///     .parse()
+///     .unwrap();
+/// assert_eq!(meta.stream, "ORDERS");
+/// assert_eq!(meta.stream_sequence, 48);
+/// assert_eq!(meta.delivered, 2);
+/// assert_eq!(meta.domain, None);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsMessageMetadata {
+    /// The JetStream domain, if the message was not delivered from the default domain
+    pub domain: Option<String>,
+    /// The hash of the account holding the stream, in the newer layout only
+    pub account_hash: Option<String>,
+    /// The name of the stream holding the message
+    pub stream: String,
+    /// The name of the consumer that delivered the message
+    pub consumer: String,
+    /// The number of times the message has been delivered, including this one
+    pub delivered: u64,
+    /// The sequence of the message in the stream
+    pub stream_sequence: u64,
+    /// The sequence of this delivery among those of the consumer
+    pub consumer_sequence: u64,
+    /// When the message was stored in the stream
+    pub timestamp: SystemTime,
+    /// The number of messages the consumer has yet to deliver
+    pub pending: u64,
+}
+
+impl JsMessageMetadata {
+    /// Parses the metadata from the reply subject of a delivered message
+    pub fn parse(reply_to: &str) -> Result<JsMessageMetadata, NatsParseError> {
+        let invalid = |why: &str| {
+            NatsParseError::new(
+                ParseErrorKind::InvalidAckSubject,
+                format!("Invalid JetStream ack subject '{}' - {}", reply_to, why),
+            )
+        };
+        let tokens: Vec<&str> = match reply_to.strip_prefix(ACK_PREFIX) {
+            Some(rest) if rest.starts_with('.') => rest[1..].split('.').collect(),
+            _ => return Err(invalid("expected it to begin with $JS.ACK")),
+        };
+        let (domain, account_hash, fields) = match tokens.len() {
+            7 => (None, None, &tokens[..]),
+            n if n >= 9 => {
+                let domain = Some(tokens[0]).filter(|d| *d != "_");
+                (domain, Some(tokens[1]), &tokens[2..9])
+            }
+            _ => return Err(invalid("unexpected number of tokens")),
+        };
+        if fields[..2].iter().any(|t| t.is_empty()) {
+            return Err(invalid("empty stream or consumer name"));
+        }
+        let number = |i: usize| {
+            fields[i]
+                .parse::<u64>()
+                .map_err(|_| invalid(&format!("'{}' is not a number", fields[i])))
+        };
+        Ok(JsMessageMetadata {
+            domain: domain.map(str::to_string),
+            account_hash: account_hash.map(str::to_string),
+            stream: fields[0].to_string(),
+            consumer: fields[1].to_string(),
+            delivered: number(2)?,
+            stream_sequence: number(3)?,
+            consumer_sequence: number(4)?,
+            timestamp: UNIX_EPOCH + Duration::from_nanos(number(5)?),
+            pending: number(6)?,
+        })
+    }
+}
+
+impl FromStr for JsMessageMetadata {
+    type Err = NatsParseError;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        JsMessageMetadata::parse(s)
+    }
+}
+
+/// A response to a message delivered by a JetStream consumer, published to the message's
+/// reply subject. Its `Display` gives the payload.
+///
+/// ```rust
+/// extern crate nats_types;
+///
+/// use nats_types::AckKind;
+/// use std::time::Duration;
+///
+/// let nak = AckKind::Nak(Some(Duration::from_secs(5)));
+/// assert_eq!(nak.to_string(), r#"-NAK {"delay":5000000000}"#);
+///
+/// let msg = AckKind::Ack
+///     .to_publish("$JS.ACK.ORDERS.EU.1.48.7.1704164645000000000.10")
+///     .unwrap();
+/// assert_eq!(msg.payload, &b"+ACK"[..]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum AckKind {
+    /// `+ACK`: the message has been processed
+    Ack,
+    /// `-NAK`: the message should be redelivered, optionally after a delay
+    Nak(Option<Duration>),
+    /// `+WPI`: the message is still being worked on, resetting the ack wait
+    Progress,
+    /// `+NXT`: acknowledges the message and asks a pull consumer for more, which are delivered
+    /// to the `reply_to` of the ack
+    Next(Option<PullRequest>),
+    /// `+TERM`: the message should never be redelivered, optionally giving a reason
+    Term(Option<String>),
+}
+
+impl AckKind {
+    /// The publish message that sends this response to the given reply subject. For `Next`,
+    /// the `reply_to` of the result should be set to the subject to deliver the messages to.
+    pub fn to_publish(&self, ack_subject: &str) -> Result<PublishMessage, NatsParseError> {
+        PublishMessage::new(
+            Subject::new(ack_subject)?,
+            None,
+            self.to_string().into_bytes(),
+        )
+    }
+}
+
+#[derive(Serialize)]
+struct NakDelay {
+    #[serde(with = "super::nanos")]
+    delay: Duration,
+}
+
+impl Display for AckKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            AckKind::Ack => write!(f, "+ACK"),
+            AckKind::Nak(None) => write!(f, "-NAK"),
+            AckKind::Nak(Some(delay)) => {
+                let json =
+                    serde_json::to_string(&NakDelay { delay: *delay }).map_err(|_| fmt::Error)?;
+                write!(f, "-NAK {}", json)
+            }
+            AckKind::Progress => write!(f, "+WPI"),
+            AckKind::Next(None) => write!(f, "+NXT"),
+            AckKind::Next(Some(req)) => {
+                let json = serde_json::to_string(req).map_err(|_| fmt::Error)?;
+                write!(f, "+NXT {}", json)
+            }
+            AckKind::Term(None) => write!(f, "+TERM"),
+            AckKind::Term(Some(reason)) => write!(f, "+TERM {}", reason),
+        }
+    }
+}
+
+fn ack_subject(reply_to: &Option<String>) -> Result<&str, NatsParseError> {
+    reply_to.as_deref().ok_or_else(|| {
+        NatsParseError::new(
+            ParseErrorKind::InvalidAckSubject,
+            "Message has no reply subject to acknowledge",
+        )
+    })
+}
+
+impl DeliveredMessage {
+    /// The JetStream metadata carried by the reply subject of a message delivered by a consumer
+    pub fn jetstream_metadata(&self) -> Result<JsMessageMetadata, NatsParseError> {
+        JsMessageMetadata::parse(ack_subject(&self.reply_to)?)
+    }
+
+    /// The publish message responding to this message with the given kind of ack
+    pub fn ack(&self, kind: &AckKind) -> Result<PublishMessage, NatsParseError> {
+        kind.to_publish(ack_subject(&self.reply_to)?)
+    }
+}
+
+impl HeaderDeliveredMessage {
+    /// The JetStream metadata carried by the reply subject of a message delivered by a consumer
+    pub fn jetstream_metadata(&self) -> Result<JsMessageMetadata, NatsParseError> {
+        JsMessageMetadata::parse(ack_subject(&self.reply_to)?)
+    }
+
+    /// The publish message responding to this message with the given kind of ack
+    pub fn ack(&self, kind: &AckKind) -> Result<PublishMessage, NatsParseError> {
+        kind.to_publish(ack_subject(&self.reply_to)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AckKind, JsMessageMetadata};
+    use crate::{DeliveredMessage, ParseErrorKind, PullRequest};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn metadata_layouts() {
+        let v1 =
+            JsMessageMetadata::parse("$JS.ACK.ORDERS.EU.1.48.7.1704164645123456789.10").unwrap();
+        assert_eq!(v1.account_hash, None);
+        assert_eq!(v1.consumer, "EU");
+        assert_eq!(v1.consumer_sequence, 7);
+        assert_eq!(v1.pending, 10);
+        assert_eq!(
+            v1.timestamp.duration_since(UNIX_EPOCH).unwrap(),
+            Duration::new(1_704_164_645, 123_456_789)
+        );
+
+        let v2 = JsMessageMetadata::parse(
+            "$JS.ACK.hub.ACCHASH.ORDERS.EU.3.48.7.1704164645123456789.10.8dNkPuGq",
+        )
+        .unwrap();
+        assert_eq!(v2.domain.as_deref(), Some("hub"));
+        assert_eq!(v2.account_hash.as_deref(), Some("ACCHASH"));
+        assert_eq!(v2.delivered, 3);
+        assert_eq!(v2.stream_sequence, v1.stream_sequence);
+
+        // the newer layout with exactly nine tokens, without the trailing random token
+        let local =
+            JsMessageMetadata::parse("$JS.ACK._.ACCHASH.ORDERS.EU.1.48.7.1704164645123456789.0")
+                .unwrap();
+        assert_eq!(local.domain, None);
+        assert_eq!(local.account_hash.as_deref(), Some("ACCHASH"));
+        assert_eq!(local.stream, "ORDERS");
+        assert_eq!(local.pending, 0);
+
+        for bad in &[
+            "_INBOX.abc",
+            "$JS.ACKED.ORDERS.EU.1.48.7.1.10",
+            "$JS.ACK.ORDERS.EU.1.48.7.1",
+            "$JS.ACK.hub.ACCHASH.ORDERS.EU.1.48.7.1",
+            "$JS.ACK.ORDERS.EU.one.48.7.1.10",
+            "$JS.ACK.ORDERS..1.48.7.1.10",
+        ] {
+            let err = JsMessageMetadata::parse(bad).unwrap_err();
+            assert_eq!(err.kind(), ParseErrorKind::InvalidAckSubject, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn ack_payloads() {
+        let msg = DeliveredMessage::new(
+            "orders.eu.1".parse().unwrap(),
+            1,
+            Some("$JS.ACK.ORDERS.EU.1.48.7.1704164645123456789.10".to_string()),
+            &b"{}"[..],
+        );
+        assert_eq!(msg.jetstream_metadata().unwrap().stream_sequence, 48);
+
+        let ack = msg.ack(&AckKind::Progress).unwrap();
+        assert_eq!(ack.subject, msg.reply_to.unwrap().as_str());
+        assert_eq!(ack.payload, &b"+WPI"[..]);
+
+        assert_eq!(AckKind::Nak(None).to_string(), "-NAK");
+        assert_eq!(AckKind::Next(None).to_string(), "+NXT");
+        assert_eq!(
+            AckKind::Next(Some(PullRequest::batch(5))).to_string(),
+            r#"+NXT {"batch":5}"#
+        );
+        assert_eq!(
+            AckKind::Term(Some("bad order".to_string())).to_string(),
+            "+TERM bad order"
+        );
+
+        let unacked = DeliveredMessage::new("orders".parse().unwrap(), 1, None, &b"{}"[..]);
+        assert!(unacked.ack(&AckKind::Ack).is_err());
+        assert!(unacked.jetstream_metadata().is_err());
+    }
+}
@@ -0,0 +1,468 @@
+use super::{is_zero, is_zero_duration, nanos, null_as_empty, JetStreamApi, StreamClusterInfo};
+use crate::{is_false, NatsParseError, PublishMessage, Subject};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Where in the stream a consumer starts delivering from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliverPolicy {
+    /// From the first message in the stream
+    #[default]
+    All,
+    /// From the last message in the stream
+    Last,
+    /// From the next message published to the stream
+    New,
+    /// From the sequence given by `opt_start_seq`
+    ByStartSequence,
+    /// From the time given by `opt_start_time`
+    ByStartTime,
+    /// From the last message on each subject in the stream
+    LastPerSubject,
+}
+
+/// How the messages delivered by a consumer are acknowledged
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AckPolicy {
+    /// Messages are never acknowledged
+    None,
+    /// Acknowledging a message acknowledges every message delivered before it
+    All,
+    /// Every message is acknowledged individually
+    #[default]
+    Explicit,
+}
+
+/// The rate at which a consumer delivers messages
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplayPolicy {
+    /// As quickly as possible
+    #[default]
+    Instant,
+    /// At the rate they were originally published
+    Original,
+}
+
+/// The configuration of a consumer. Pull consumers leave `deliver_subject` unset. Limits and
+/// durations of zero leave the server's default in place.
+///
+/// ```rust
+/// extern crate nats_types;
+///
+/// use nats_types::{AckPolicy, ConsumerConfig};
+/// use std::time::Duration;
+///
+/// let config = ConsumerConfig {
+///     filter_subject: Some("orders.eu.>".to_string()),
+///     ack_wait: Duration::from_secs(30),
+///     max_ack_pending: 100,
+///     ..ConsumerConfig::durable("EU")
+/// };
+/// assert_eq!(config.ack_policy, AckPolicy::Explicit);
+/// let json = serde_json::to_string(&config).unwrap();
+/// assert!(json.contains(r#""ack_wait":30000000000"#));
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ConsumerConfig {
+    /// The name of a consumer that survives periods without subscribers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub durable_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub deliver_policy: DeliverPolicy,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opt_start_seq: Option<u64>,
+    /// An RFC 3339 timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opt_start_time: Option<String>,
+    #[serde(default)]
+    pub ack_policy: AckPolicy,
+    /// How long the server waits for an acknowledgement before redelivering
+    #[serde(default, with = "nanos", skip_serializing_if = "is_zero_duration")]
+    pub ack_wait: Duration,
+    /// The number of times a message is delivered before it is given up on, `-1` for unlimited
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub max_deliver: i64,
+    /// The delays between redeliveries, the last of which is repeated. Overrides `ack_wait`.
+    #[serde(default, with = "nanos::seq", skip_serializing_if = "Vec::is_empty")]
+    pub backoff: Vec<Duration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter_subject: Option<String>,
+    /// Several filters, in place of `filter_subject`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filter_subjects: Vec<String>,
+    #[serde(default)]
+    pub replay_policy: ReplayPolicy,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub rate_limit_bps: u64,
+    /// The percentage of acknowledgements to sample for observability, e.g. `"10%"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_freq: Option<String>,
+    /// The number of pull requests that may wait for messages at once
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub max_waiting: i64,
+    /// The number of messages that may be delivered without being acknowledged
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub max_ack_pending: i64,
+    /// Delivers only the headers of messages, with the payload size in `Nats-Msg-Size`
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub headers_only: bool,
+    /// The largest `batch` a pull request may ask for
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub max_batch: i64,
+    /// The longest `expires` a pull request may ask for
+    #[serde(default, with = "nanos", skip_serializing_if = "is_zero_duration")]
+    pub max_expires: Duration,
+    /// The largest `max_bytes` a pull request may ask for
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub max_bytes: i64,
+    /// How long the consumer may go without activity before it is removed
+    #[serde(default, with = "nanos", skip_serializing_if = "is_zero_duration")]
+    pub inactive_threshold: Duration,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub num_replicas: usize,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub mem_storage: bool,
+    /// The subject a push consumer delivers to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deliver_subject: Option<String>,
+    /// The queue group a push consumer delivers to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deliver_group: Option<String>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub flow_control: bool,
+    #[serde(default, with = "nanos", skip_serializing_if = "is_zero_duration")]
+    pub idle_heartbeat: Duration,
+    /// An RFC 3339 timestamp until which the consumer delivers nothing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pause_until: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+    /// Any fields not known to this crate, kept so that they survive a round trip
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ConsumerConfig {
+    /// A durable pull consumer with the given name, delivering every message with explicit
+    /// acknowledgement
+    pub fn durable<S: Into<String>>(name: S) -> ConsumerConfig {
+        ConsumerConfig {
+            durable_name: Some(name.into()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Whether a `CONSUMER.CREATE` request may create a new consumer, update an existing one, or
+/// either
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum ConsumerAction {
+    Create,
+    Update,
+}
+
+/// The body of a `CONSUMER.CREATE` request
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConsumerCreateRequest {
+    pub stream_name: String,
+    pub config: ConsumerConfig,
+    /// Either, when `None`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<ConsumerAction>,
+}
+
+/// The sequences of a consumer's progress through its stream
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct SequenceInfo {
+    pub consumer_seq: u64,
+    pub stream_seq: u64,
+    /// An RFC 3339 timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_active: Option<String>,
+}
+
+/// The configuration and state of a consumer, as returned by `CONSUMER.CREATE` and
+/// `CONSUMER.INFO`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConsumerInfo {
+    pub stream_name: String,
+    pub name: String,
+    /// An RFC 3339 timestamp
+    pub created: String,
+    pub config: ConsumerConfig,
+    /// The last message delivered
+    pub delivered: SequenceInfo,
+    /// The last message before which every message has been acknowledged
+    pub ack_floor: SequenceInfo,
+    pub num_ack_pending: u64,
+    pub num_redelivered: u64,
+    /// The number of pull requests waiting for messages
+    pub num_waiting: u64,
+    /// The number of messages yet to be delivered
+    pub num_pending: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<StreamClusterInfo>,
+    /// Indicates whether a push consumer has a subscriber
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub push_bound: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub paused: bool,
+    #[serde(default, with = "nanos", skip_serializing_if = "is_zero_duration")]
+    pub pause_remaining: Duration,
+}
+
+/// The response to `CONSUMER.DELETE`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConsumerDeleteResponse {
+    pub success: bool,
+}
+
+/// The optional body of a `CONSUMER.LIST` request, which is paged
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ConsumerListRequest {
+    #[serde(default)]
+    pub offset: usize,
+}
+
+/// A page of the response to `CONSUMER.LIST`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConsumerListResponse {
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub consumers: Vec<ConsumerInfo>,
+    /// Consumers that could not be reached
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<String>,
+}
+
+/// The optional body of a `CONSUMER.NAMES` request, which is paged
+pub type ConsumerNamesRequest = ConsumerListRequest;
+
+/// A page of the response to `CONSUMER.NAMES`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConsumerNamesResponse {
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub consumers: Vec<String>,
+}
+
+/// The body of a `CONSUMER.MSG.NEXT` request, asking a pull consumer to deliver a batch of
+/// messages to the request's reply subject. Without `expires` or `no_wait`, the request waits
+/// until the whole batch has been delivered.
+///
+/// ```rust
+/// extern crate nats_types;
+///
+/// use nats_types::{JetStreamApi, PullRequest};
+/// use std::time::Duration;
+///
+/// let req = PullRequest {
+///     expires: Duration::from_secs(5),
+///     ..PullRequest::batch(10)
+/// };
+/// let msg = req
+///     .to_publish(&JetStreamApi::new(), "ORDERS", "EU", "_INBOX.pull")
+///     .unwrap();
+/// assert_eq!(msg.subject, "$JS.API.CONSUMER.MSG.NEXT.ORDERS.EU");
+/// assert_eq!(msg.payload, &br#"{"batch":10,"expires":5000000000}"#[..]);
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PullRequest {
+    pub batch: usize,
+    /// How long the request waits for messages
+    #[serde(default, with = "nanos", skip_serializing_if = "is_zero_duration")]
+    pub expires: Duration,
+    /// Ends the request as soon as no more messages are available
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub no_wait: bool,
+    /// Limits the total size of the batch
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub max_bytes: usize,
+    /// The interval at which the server sends heartbeats while the request waits. Must be
+    /// shorter than `expires`.
+    #[serde(default, with = "nanos", skip_serializing_if = "is_zero_duration")]
+    pub idle_heartbeat: Duration,
+}
+
+impl PullRequest {
+    /// A request for up to the given number of messages
+    pub fn batch(batch: usize) -> PullRequest {
+        PullRequest {
+            batch,
+            ..Default::default()
+        }
+    }
+
+    /// The publish message that sends this request to the given consumer, whose messages are
+    /// delivered to `reply_to`
+    pub fn to_publish(
+        &self,
+        api: &JetStreamApi,
+        stream: &str,
+        consumer: &str,
+        reply_to: &str,
+    ) -> Result<PublishMessage, NatsParseError> {
+        let payload = serde_json::to_vec(self).expect("pull requests always serialize");
+        PublishMessage::new(
+            api.consumer_next(stream, consumer)?,
+            Some(reply_to.to_string()),
+            payload,
+        )
+    }
+}
+
+impl JetStreamApi {
+    /// `CONSUMER.CREATE.<stream>.<consumer>`, taking a `ConsumerCreateRequest` and returning a
+    /// `ConsumerInfo`
+    pub fn consumer_create(&self, stream: &str, consumer: &str) -> Result<Subject, NatsParseError> {
+        self.consumer_subject("CONSUMER.CREATE", stream, consumer)
+    }
+
+    /// `CONSUMER.CREATE.<stream>`, taking a `ConsumerCreateRequest` for a consumer without a
+    /// name and returning a `ConsumerInfo`
+    pub fn consumer_create_ephemeral(&self, stream: &str) -> Result<Subject, NatsParseError> {
+        self.named_subject("CONSUMER.CREATE", "stream", stream)
+    }
+
+    /// `CONSUMER.INFO.<stream>.<consumer>`, taking no body and returning a `ConsumerInfo`
+    pub fn consumer_info(&self, stream: &str, consumer: &str) -> Result<Subject, NatsParseError> {
+        self.consumer_subject("CONSUMER.INFO", stream, consumer)
+    }
+
+    /// `CONSUMER.DELETE.<stream>.<consumer>`, taking no body and returning a
+    /// `ConsumerDeleteResponse`
+    pub fn consumer_delete(&self, stream: &str, consumer: &str) -> Result<Subject, NatsParseError> {
+        self.consumer_subject("CONSUMER.DELETE", stream, consumer)
+    }
+
+    /// `CONSUMER.LIST.<stream>`, taking an optional `ConsumerListRequest` and returning a
+    /// `ConsumerListResponse`
+    pub fn consumer_list(&self, stream: &str) -> Result<Subject, NatsParseError> {
+        self.named_subject("CONSUMER.LIST", "stream", stream)
+    }
+
+    /// `CONSUMER.NAMES.<stream>`, taking an optional `ConsumerNamesRequest` and returning a
+    /// `ConsumerNamesResponse`
+    pub fn consumer_names(&self, stream: &str) -> Result<Subject, NatsParseError> {
+        self.named_subject("CONSUMER.NAMES", "stream", stream)
+    }
+
+    /// `CONSUMER.MSG.NEXT.<stream>.<consumer>`, taking a `PullRequest`. Messages are delivered
+    /// to the request's reply subject.
+    pub fn consumer_next(&self, stream: &str, consumer: &str) -> Result<Subject, NatsParseError> {
+        self.consumer_subject("CONSUMER.MSG.NEXT", stream, consumer)
+    }
+
+    fn consumer_subject(
+        &self,
+        operation: &str,
+        stream: &str,
+        consumer: &str,
+    ) -> Result<Subject, NatsParseError> {
+        super::validate_name("stream", stream)?;
+        self.named_subject(&format!("{}.{}", operation, stream), "consumer", consumer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        AckPolicy, ConsumerAction, ConsumerConfig, ConsumerCreateRequest, ConsumerInfo,
+        DeliverPolicy, PullRequest, ReplayPolicy,
+    };
+    use crate::{parse_api_response, JetStreamApi};
+    use std::time::Duration;
+
+    // as sent by nats-server 2.11
+    const CONSUMER_INFO: &str = r#"{"type":"io.nats.jetstream.api.v1.consumer_info_response","stream_name":"ORDERS","name":"EU","created":"2024-01-02T03:04:05.123Z","config":{"durable_name":"EU","name":"EU","deliver_policy":"by_start_sequence","opt_start_seq":42,"ack_policy":"explicit","ack_wait":30000000000,"max_deliver":-1,"backoff":[1000000000,5000000000],"filter_subjects":["orders.eu.>","orders.uk.>"],"replay_policy":"instant","max_waiting":512,"max_ack_pending":1000,"inactive_threshold":300000000000,"num_replicas":0,"priority_policy":"none"},"delivered":{"consumer_seq":7,"stream_seq":48,"last_active":"2024-01-02T03:05:00Z"},"ack_floor":{"consumer_seq":5,"stream_seq":46},"num_ack_pending":2,"num_redelivered":1,"num_waiting":1,"num_pending":10,"cluster":{"leader":"n1"},"paused":true,"pause_remaining":60000000000,"ts":"2024-01-02T03:05:01Z"}"#;
+
+    #[test]
+    fn consumer_info_response() {
+        let info: ConsumerInfo = parse_api_response(CONSUMER_INFO.as_bytes()).unwrap();
+        let config = &info.config;
+        assert_eq!(config.deliver_policy, DeliverPolicy::ByStartSequence);
+        assert_eq!(config.opt_start_seq, Some(42));
+        assert_eq!(config.ack_policy, AckPolicy::Explicit);
+        assert_eq!(config.replay_policy, ReplayPolicy::Instant);
+        assert_eq!(config.max_deliver, -1);
+        assert_eq!(
+            config.backoff,
+            [Duration::from_secs(1), Duration::from_secs(5)]
+        );
+        assert_eq!(config.filter_subjects.len(), 2);
+        assert_eq!(config.inactive_threshold, Duration::from_secs(300));
+        assert!(config.extra.contains_key("priority_policy"));
+        assert_eq!(info.delivered.stream_seq, 48);
+        assert_eq!(info.ack_floor.last_active, None);
+        assert_eq!(info.num_pending, 10);
+        assert!(info.paused);
+        assert_eq!(info.pause_remaining, Duration::from_secs(60));
+
+        let json = serde_json::to_string(&info).unwrap();
+        let again: ConsumerInfo = serde_json::from_str(&json).unwrap();
+        assert_eq!(again, info);
+    }
+
+    #[test]
+    fn consumer_requests() {
+        let api = JetStreamApi::with_domain("hub").unwrap();
+        assert_eq!(
+            api.consumer_create("ORDERS", "EU").unwrap(),
+            "$JS.hub.API.CONSUMER.CREATE.ORDERS.EU"
+        );
+        assert_eq!(
+            api.consumer_create_ephemeral("ORDERS").unwrap(),
+            "$JS.hub.API.CONSUMER.CREATE.ORDERS"
+        );
+        assert_eq!(
+            api.consumer_names("ORDERS").unwrap(),
+            "$JS.hub.API.CONSUMER.NAMES.ORDERS"
+        );
+        assert!(api.consumer_info("ORDERS", "E.U").is_err());
+        assert!(api.consumer_delete("ORD>", "EU").is_err());
+
+        let req = ConsumerCreateRequest {
+            stream_name: "ORDERS".to_string(),
+            config: ConsumerConfig {
+                deliver_policy: DeliverPolicy::LastPerSubject,
+                backoff: vec![Duration::from_millis(500)],
+                ..ConsumerConfig::durable("EU")
+            },
+            action: Some(ConsumerAction::Create),
+        };
+        assert_eq!(
+            serde_json::to_string(&req).unwrap(),
+            r#"{"stream_name":"ORDERS","config":{"durable_name":"EU","deliver_policy":"last_per_subject","ack_policy":"explicit","backoff":[500000000],"replay_policy":"instant"},"action":"create"}"#
+        );
+    }
+
+    #[test]
+    fn pull_request() {
+        let req = PullRequest {
+            no_wait: true,
+            max_bytes: 1024,
+            ..PullRequest::batch(1)
+        };
+        let msg = req
+            .to_publish(&JetStreamApi::new(), "ORDERS", "EU", "_INBOX.abc")
+            .unwrap();
+        assert_eq!(msg.reply_to, Some("_INBOX.abc".to_string()));
+        assert_eq!(
+            msg.payload,
+            &br#"{"batch":1,"no_wait":true,"max_bytes":1024}"#[..]
+        );
+        assert_eq!(msg.payload_size, msg.payload.len());
+    }
+}
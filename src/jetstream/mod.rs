@@ -0,0 +1,322 @@
+use crate::{NatsParseError, ParseErrorKind, Subject};
+use serde::de::DeserializeOwned;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+mod ack;
+mod consumer;
+mod publish;
+mod stream;
+
+pub use self::ack::{AckKind, JsMessageMetadata, ACK_PREFIX};
+pub use self::consumer::{
+    AckPolicy, ConsumerAction, ConsumerConfig, ConsumerCreateRequest, ConsumerDeleteResponse,
+    ConsumerInfo, ConsumerListRequest, ConsumerListResponse, ConsumerNamesRequest,
+    ConsumerNamesResponse, DeliverPolicy, PullRequest, ReplayPolicy, SequenceInfo,
+};
+pub use self::publish::{
+    PubAck, PublishOptions, HEADER_EXPECTED_LAST_MSG_ID, HEADER_EXPECTED_LAST_SEQUENCE,
+    HEADER_EXPECTED_LAST_SUBJECT_SEQUENCE, HEADER_EXPECTED_STREAM, HEADER_MSG_ID, HEADER_ROLLUP,
+    ROLLUP_ALL, ROLLUP_SUBJECT,
+};
+pub use self::stream::{
+    Compression, DiscardPolicy, External, PeerInfo, Placement, Republish, RetentionPolicy,
+    StorageType, StreamClusterInfo, StreamConfig, StreamDeleteResponse, StreamInfo,
+    StreamInfoRequest, StreamListRequest, StreamListResponse, StreamNamesRequest,
+    StreamNamesResponse, StreamPurgeRequest, StreamPurgeResponse, StreamSource, StreamSourceInfo,
+    StreamState, SubjectTransform,
+};
+
+/// The prefix of the JetStream API subjects in the default domain
+pub const DEFAULT_API_PREFIX: &str = "$JS.API";
+
+/// Produces the subjects of the JetStream API, to which requests are published with a reply
+/// subject on which the response is delivered. The API may be reached in the local account,
+/// in another JetStream domain, or through an account import under a custom prefix.
+///
+/// ```rust
+/// extern crate nats_types;
+///
+/// use nats_types::JetStreamApi;
+///
+/// let api = JetStreamApi::new();
+/// assert_eq!(api.stream_create("ORDERS").unwrap(), "$JS.API.STREAM.CREATE.ORDERS");
+///
+/// let hub = JetStreamApi::with_domain("hub").unwrap();
+/// assert_eq!(hub.stream_names(), "$JS.hub.API.STREAM.NAMES");
+///
+/// assert!(api.stream_info("ORDERS.*").is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct JetStreamApi {
+    prefix: String,
+}
+
+impl Default for JetStreamApi {
+    fn default() -> Self {
+        JetStreamApi::new()
+    }
+}
+
+impl JetStreamApi {
+    /// The API of the local account, under `$JS.API`
+    pub fn new() -> JetStreamApi {
+        JetStreamApi {
+            prefix: DEFAULT_API_PREFIX.to_string(),
+        }
+    }
+
+    /// The API of the given JetStream domain, under `$JS.<domain>.API`
+    pub fn with_domain(domain: &str) -> Result<JetStreamApi, NatsParseError> {
+        validate_name("domain", domain)?;
+        Ok(JetStreamApi {
+            prefix: format!("$JS.{}.API", domain),
+        })
+    }
+
+    /// The API under a custom prefix, such as one imported from another account
+    pub fn with_prefix(prefix: &str) -> Result<JetStreamApi, NatsParseError> {
+        let prefix = Subject::literal(prefix.trim_end_matches('.'))?;
+        Ok(JetStreamApi {
+            prefix: prefix.into_string(),
+        })
+    }
+
+    /// The prefix of every API subject, without a trailing `.`
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// The subject of an API operation that takes no name, e.g. `STREAM.NAMES`
+    pub(crate) fn subject(&self, operation: &str) -> Subject {
+        Subject::from_validated(format!("{}.{}", self.prefix, operation))
+    }
+
+    /// The subject of an API operation on a named stream or consumer
+    pub(crate) fn named_subject(
+        &self,
+        operation: &str,
+        kind: &str,
+        name: &str,
+    ) -> Result<Subject, NatsParseError> {
+        validate_name(kind, name)?;
+        Ok(self.subject(&format!("{}.{}", operation, name)))
+    }
+}
+
+// Stream, consumer and domain names become a single subject token, so they may not contain
+// anything that would split or widen it
+pub(crate) fn validate_name(kind: &str, name: &str) -> Result<(), NatsParseError> {
+    if name.is_empty()
+        || name
+            .chars()
+            .any(|c| c == '.' || c == '*' || c == '>' || c.is_whitespace())
+    {
+        Err(NatsParseError::new(
+            ParseErrorKind::InvalidSubject,
+            format!(
+                "Invalid {} name '{}' - names may not be empty or contain '.', '*', '>' or whitespace",
+                kind, name
+            ),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// The error reported by the JetStream API in place of a response
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ApiError {
+    /// The HTTP-like status code, e.g. 404
+    pub code: u16,
+    /// The JetStream specific error code, identifying the error precisely
+    #[serde(default)]
+    pub err_code: u32,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl ApiError {
+    pub const JETSTREAM_NOT_ENABLED: u32 = 10076;
+    pub const STREAM_NOT_FOUND: u32 = 10059;
+    pub const STREAM_NAME_IN_USE: u32 = 10058;
+    pub const CONSUMER_NOT_FOUND: u32 = 10014;
+    pub const CONSUMER_NAME_EXISTS: u32 = 10013;
+    pub const NO_MESSAGE_FOUND: u32 = 10037;
+    pub const STREAM_WRONG_LAST_SEQUENCE: u32 = 10071;
+}
+
+impl Error for ApiError {}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "JetStream API error {} ({}): {}",
+            self.code, self.err_code, self.description
+        )
+    }
+}
+
+/// Indicates a JetStream API response reported an error or could not be understood
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum JetStreamError {
+    /// The API reported an error
+    Api(ApiError),
+    /// The response could not be deserialized
+    InvalidResponse(serde_json::Error),
+    /// The request was published to a subject with no subscribers, e.g. one not captured by
+    /// any stream
+    NoResponders,
+}
+
+impl Error for JetStreamError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            JetStreamError::Api(e) => Some(e),
+            JetStreamError::InvalidResponse(e) => Some(e),
+            JetStreamError::NoResponders => None,
+        }
+    }
+}
+
+impl Display for JetStreamError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            JetStreamError::Api(e) => write!(f, "{}", e),
+            JetStreamError::InvalidResponse(e) => {
+                write!(f, "Invalid JetStream API response - {}", e)
+            }
+            JetStreamError::NoResponders => write!(f, "No responders to JetStream request"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ErrorEnvelope {
+    error: Option<ApiError>,
+}
+
+/// Parses the payload of a JetStream API response, returning the error it carries if any
+///
+/// ```rust
+/// extern crate nats_types;
+///
+/// use nats_types::{parse_api_response, ApiError, JetStreamError, StreamDeleteResponse};
+///
+/// let ok: StreamDeleteResponse = parse_api_response(br#"{"success":true}"#).unwrap();
+/// assert!(ok.success);
+///
+/// let err = parse_api_response::<StreamDeleteResponse>(
+///     br#"{"type":"io.nats.jetstream.api.v1.stream_delete_response","error":{"code":404,"err_code":10059,"description":"stream not found"}}"#,
+/// );
+/// match err {
+///     Err(JetStreamError::Api(e)) => assert_eq!(e.err_code, ApiError::STREAM_NOT_FOUND),
+///     _ => panic!("expected an API error"),
+/// }
+/// ```
+pub fn parse_api_response<T: DeserializeOwned>(payload: &[u8]) -> Result<T, JetStreamError> {
+    let envelope: ErrorEnvelope =
+        serde_json::from_slice(payload).map_err(JetStreamError::InvalidResponse)?;
+    match envelope.error {
+        Some(e) => Err(JetStreamError::Api(e)),
+        None => serde_json::from_slice(payload).map_err(JetStreamError::InvalidResponse),
+    }
+}
+
+// Durations are expressed in nanoseconds by the JetStream API
+pub(crate) mod nanos {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::convert::TryFrom;
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(d: &Duration, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_i64(i64::try_from(d.as_nanos()).unwrap_or(i64::MAX))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Duration, D::Error> {
+        // negative values are used by the server to mean "never", treated here as zero
+        let nanos = i64::deserialize(d)?;
+        Ok(Duration::from_nanos(nanos.max(0) as u64))
+    }
+
+    // A list of durations, such as a consumer's backoff
+    pub mod seq {
+        use serde::{Deserialize, Deserializer, Serializer};
+        use std::convert::TryFrom;
+        use std::time::Duration;
+
+        pub fn serialize<S: Serializer>(ds: &[Duration], s: S) -> Result<S::Ok, S::Error> {
+            s.collect_seq(
+                ds.iter()
+                    .map(|d| i64::try_from(d.as_nanos()).unwrap_or(i64::MAX)),
+            )
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<Duration>, D::Error> {
+            let nanos = Option::<Vec<i64>>::deserialize(d)?.unwrap_or_default();
+            Ok(nanos
+                .into_iter()
+                .map(|n| Duration::from_nanos(n.max(0) as u64))
+                .collect())
+        }
+    }
+}
+
+// The server sends `null` rather than `[]` for an empty page
+fn null_as_empty<'de, D, T>(d: D) -> Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::Deserialize<'de>,
+{
+    serde::Deserialize::deserialize(d).map(Option::unwrap_or_default)
+}
+
+fn is_zero<T: Default + PartialEq>(n: &T) -> bool {
+    *n == T::default()
+}
+
+fn is_zero_duration(d: &std::time::Duration) -> bool {
+    d.as_nanos() == 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_api_response, validate_name, JetStreamApi, JetStreamError};
+    use crate::StreamNamesResponse;
+
+    #[test]
+    fn api_subjects() {
+        let api = JetStreamApi::with_prefix("$JS.import.API.").unwrap();
+        assert_eq!(api.prefix(), "$JS.import.API");
+        assert_eq!(
+            api.stream_purge("ORDERS").unwrap(),
+            "$JS.import.API.STREAM.PURGE.ORDERS"
+        );
+        assert!(JetStreamApi::with_domain("a.b").is_err());
+        assert!(JetStreamApi::with_prefix("$JS.*").is_err());
+        assert!(validate_name("stream", "ORDERS_1-x").is_ok());
+        assert!(validate_name("stream", "").is_err());
+        assert!(validate_name("stream", "OR DERS").is_err());
+    }
+
+    #[test]
+    fn api_responses() {
+        let names: StreamNamesResponse =
+            parse_api_response(br#"{"total":2,"offset":0,"limit":1024,"streams":["A","B"]}"#)
+                .unwrap();
+        assert_eq!(names.streams, ["A", "B"]);
+
+        let empty: StreamNamesResponse =
+            parse_api_response(br#"{"total":0,"offset":0,"limit":1024,"streams":null}"#).unwrap();
+        assert!(empty.streams.is_empty());
+
+        assert!(matches!(
+            parse_api_response::<StreamNamesResponse>(b"nope"),
+            Err(JetStreamError::InvalidResponse(_))
+        ));
+    }
+}
@@ -0,0 +1,245 @@
+use super::{parse_api_response, JetStreamError};
+use crate::{is_false, DeliveredMessage, Encode, HeaderDeliveredMessage, HeaderMap};
+use crate::{HeaderPublishMessage, NatsParseError, Payload, Subject};
+
+/// The header carrying the id by which a stream discards duplicate messages within its
+/// `duplicate_window`
+pub const HEADER_MSG_ID: &str = "Nats-Msg-Id";
+/// The header rejecting a publish unless it is stored in the named stream
+pub const HEADER_EXPECTED_STREAM: &str = "Nats-Expected-Stream";
+/// The header rejecting a publish unless the last message in the stream has this sequence
+pub const HEADER_EXPECTED_LAST_SEQUENCE: &str = "Nats-Expected-Last-Sequence";
+/// The header rejecting a publish unless the last message on its subject has this sequence
+pub const HEADER_EXPECTED_LAST_SUBJECT_SEQUENCE: &str = "Nats-Expected-Last-Subject-Sequence";
+/// The header rejecting a publish unless the last message in the stream has this id
+pub const HEADER_EXPECTED_LAST_MSG_ID: &str = "Nats-Expected-Last-Msg-Id";
+/// The header asking a stream that allows rollups to remove the messages before this one,
+/// either on its subject or in the whole stream
+pub const HEADER_ROLLUP: &str = "Nats-Rollup";
+/// The `Nats-Rollup` value removing the earlier messages on the same subject
+pub const ROLLUP_SUBJECT: &str = "sub";
+/// The `Nats-Rollup` value removing every earlier message in the stream
+pub const ROLLUP_ALL: &str = "all";
+
+/// The acknowledgement sent to the reply subject of a message stored by a stream
+///
+/// ```rust
+/// extern crate nats_types;
+///
+/// use nats_types::{DeliveredMessage, PubAck};
+///
+/// let reply = DeliveredMessage::new(
+///     "_INBOX.abc".parse().unwrap(),
+///     1,
+///     None,
+///     &br#"{"stream":"ORDERS","seq":42}"#[..],
+/// );
+/// let ack = reply.pub_ack().unwrap();
+/// assert_eq!(ack.stream, "ORDERS");
+/// assert_eq!(ack.seq, 42);
+/// assert!(!ack.duplicate);
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PubAck {
+    pub stream: String,
+    /// The sequence the message was stored at, or was first stored at if it is a duplicate
+    pub seq: u64,
+    /// Indicates the message was discarded as a duplicate of one with the same `Nats-Msg-Id`
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub duplicate: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+}
+
+impl PubAck {
+    /// Parses the payload of a reply to a publish, returning the API error it carries if any,
+    /// e.g. when an expected sequence did not match
+    pub fn parse(payload: &[u8]) -> Result<PubAck, JetStreamError> {
+        parse_api_response(payload)
+    }
+}
+
+impl DeliveredMessage {
+    /// Parses this message as the reply to a publish to a stream
+    pub fn pub_ack(&self) -> Result<PubAck, JetStreamError> {
+        PubAck::parse(&self.payload)
+    }
+}
+
+impl HeaderDeliveredMessage {
+    /// Parses this message as the reply to a publish to a stream. A no responders status means
+    /// no stream captures the subject published to.
+    pub fn pub_ack(&self) -> Result<PubAck, JetStreamError> {
+        if self.headers.is_no_responders() {
+            return Err(JetStreamError::NoResponders);
+        }
+        PubAck::parse(&self.payload)
+    }
+}
+
+/// The options of a publish to a stream, which are sent as headers. A publish is rejected
+/// with an API error if any of its expectations are not met.
+///
+/// ```rust
+/// extern crate nats_types;
+///
+/// use nats_types::PublishOptions;
+///
+/// let options = PublishOptions {
+///     msg_id: Some("order-1234".to_string()),
+///     expected_last_subject_sequence: Some(41),
+///     ..Default::default()
+/// };
+/// let msg = options
+///     .to_publish(
+///         "orders.eu.1234".parse().unwrap(),
+///         Some("_INBOX.abc".to_string()),
+///         &b"{}"[..],
+///     )
+///     .unwrap();
+/// assert_eq!(msg.headers.get("Nats-Msg-Id"), Some("order-1234"));
+/// assert_eq!(msg.headers.get("Nats-Expected-Last-Subject-Sequence"), Some("41"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PublishOptions {
+    /// Sent as `Nats-Msg-Id`
+    pub msg_id: Option<String>,
+    /// Sent as `Nats-Expected-Stream`
+    pub expected_stream: Option<String>,
+    /// Sent as `Nats-Expected-Last-Sequence`
+    pub expected_last_sequence: Option<u64>,
+    /// Sent as `Nats-Expected-Last-Subject-Sequence`. Zero expects no message on the subject.
+    pub expected_last_subject_sequence: Option<u64>,
+    /// Sent as `Nats-Expected-Last-Msg-Id`
+    pub expected_last_msg_id: Option<String>,
+}
+
+impl PublishOptions {
+    /// Sets the headers for these options, replacing any already present. Fails, leaving the
+    /// headers untouched, if an option is not a valid header value.
+    pub fn apply(&self, headers: &mut HeaderMap) -> Result<(), NatsParseError> {
+        let mut updated = headers.clone();
+        if let Some(id) = &self.msg_id {
+            updated.insert(HEADER_MSG_ID, id.as_str())?;
+        }
+        if let Some(stream) = &self.expected_stream {
+            updated.insert(HEADER_EXPECTED_STREAM, stream.as_str())?;
+        }
+        if let Some(seq) = self.expected_last_sequence {
+            updated.insert(HEADER_EXPECTED_LAST_SEQUENCE, seq.to_string())?;
+        }
+        if let Some(seq) = self.expected_last_subject_sequence {
+            updated.insert(HEADER_EXPECTED_LAST_SUBJECT_SEQUENCE, seq.to_string())?;
+        }
+        if let Some(id) = &self.expected_last_msg_id {
+            updated.insert(HEADER_EXPECTED_LAST_MSG_ID, id.as_str())?;
+        }
+        *headers = updated;
+        Ok(())
+    }
+
+    /// A header publish of the payload carrying only these options
+    pub fn to_publish(
+        &self,
+        subject: Subject,
+        reply_to: Option<String>,
+        payload: impl Into<Payload>,
+    ) -> Result<HeaderPublishMessage, NatsParseError> {
+        HeaderPublishMessage::new(subject, reply_to, HeaderMap::new(), payload)?.with_options(self)
+    }
+}
+
+impl HeaderPublishMessage {
+    /// Adds the headers for the given publish options, replacing any already present
+    pub fn with_options(
+        mut self,
+        options: &PublishOptions,
+    ) -> Result<HeaderPublishMessage, NatsParseError> {
+        options.apply(&mut self.headers)?;
+        self.header_size = self.headers.encoded_len();
+        self.total_size = self.header_size + self.payload.len();
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PubAck, PublishOptions};
+    use crate::{
+        ApiError, DeliveredMessage, HeaderDeliveredMessage, HeaderMap, HeaderPublishMessage,
+        JetStreamError, ProtocolMessage,
+    };
+
+    #[test]
+    fn pub_ack_replies() {
+        let ack = PubAck::parse(br#"{"stream":"ORDERS","domain":"hub","seq":7,"duplicate":true}"#)
+            .unwrap();
+        assert!(ack.duplicate);
+        assert_eq!(ack.domain.as_deref(), Some("hub"));
+
+        let reply = DeliveredMessage::new(
+            "_INBOX.abc".parse().unwrap(),
+            1,
+            None,
+            &br#"{"error":{"code":400,"err_code":10071,"description":"wrong last sequence: 6"},"stream":"ORDERS","seq":0}"#[..],
+        );
+        match reply.pub_ack() {
+            Err(JetStreamError::Api(e)) => {
+                assert_eq!(e.err_code, ApiError::STREAM_WRONG_LAST_SEQUENCE)
+            }
+            other => panic!("expected an API error, got {:?}", other),
+        }
+
+        let no_stream = HeaderDeliveredMessage::new(
+            "_INBOX.abc".parse().unwrap(),
+            1,
+            None,
+            HeaderMap::with_status(503, None).unwrap(),
+            &b""[..],
+        );
+        assert!(matches!(
+            no_stream.pub_ack(),
+            Err(JetStreamError::NoResponders)
+        ));
+    }
+
+    #[test]
+    fn publish_options_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("Nats-Msg-Id", "stale").unwrap();
+        headers.insert("X-Trace", "abc").unwrap();
+        let options = PublishOptions {
+            msg_id: Some("1".to_string()),
+            expected_stream: Some("ORDERS".to_string()),
+            expected_last_sequence: Some(6),
+            expected_last_msg_id: Some("0".to_string()),
+            ..Default::default()
+        };
+        let msg =
+            HeaderPublishMessage::new("orders.eu".parse().unwrap(), None, headers, &b"hello"[..])
+                .unwrap()
+                .with_options(&options)
+                .unwrap();
+        assert_eq!(
+            msg.headers.to_string(),
+            "NATS/1.0\r\nNats-Msg-Id: 1\r\nX-Trace: abc\r\nNats-Expected-Stream: ORDERS\r\nNats-Expected-Last-Sequence: 6\r\nNats-Expected-Last-Msg-Id: 0\r\n\r\n"
+        );
+
+        // sizes are kept consistent with the new headers
+        let wire = msg.to_string();
+        match ProtocolMessage::parse(wire.as_bytes()).unwrap() {
+            ProtocolMessage::HeaderPublish(parsed) => assert_eq!(parsed, msg),
+            other => panic!("expected HPUB, got {:?}", other),
+        }
+
+        // an id can't smuggle in another header, and a failed apply changes nothing
+        let mut headers = msg.headers.clone();
+        let injected = PublishOptions {
+            expected_last_sequence: Some(7),
+            msg_id: Some("2\r\nNats-Rollup: all".to_string()),
+            ..Default::default()
+        };
+        assert!(injected.apply(&mut headers).is_err());
+        assert_eq!(headers, msg.headers);
+    }
+}
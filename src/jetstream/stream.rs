@@ -0,0 +1,521 @@
+use super::{is_zero, is_zero_duration, nanos, null_as_empty, ApiError, JetStreamApi};
+use crate::{is_false, NatsParseError, Subject};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// How messages are retained by a stream
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum RetentionPolicy {
+    /// Messages are kept until a limit is reached
+    Limits,
+    /// Messages are kept while there are consumers that have yet to acknowledge them
+    Interest,
+    /// Messages are removed once acknowledged by any consumer
+    #[serde(rename = "workqueue")]
+    WorkQueue,
+}
+
+/// What a stream does with new messages once a limit is reached
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscardPolicy {
+    /// The oldest messages are removed to make room
+    Old,
+    /// New messages are rejected
+    New,
+}
+
+/// Where a stream keeps its messages
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageType {
+    File,
+    Memory,
+}
+
+/// How a stream compresses its messages at rest
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    None,
+    S2,
+}
+
+/// Constrains the servers a stream is placed on
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Placement {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+/// Maps the subjects of messages from `src` to `dest`, which may refer to the wildcards of
+/// `src` with `{{wildcard(n)}}`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SubjectTransform {
+    pub src: String,
+    pub dest: String,
+}
+
+/// Republishes the messages stored by a stream, or just their headers, to another subject
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Republish {
+    pub src: String,
+    pub dest: String,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub headers_only: bool,
+}
+
+/// The API prefix and delivery subject prefix of a stream sourced from another account or
+/// domain
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct External {
+    pub api: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deliver: Option<String>,
+}
+
+/// A stream that is mirrored or sourced into another
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct StreamSource {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opt_start_seq: Option<u64>,
+    /// An RFC 3339 timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opt_start_time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter_subject: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subject_transforms: Vec<SubjectTransform>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external: Option<External>,
+}
+
+/// The configuration of a stream, as sent to `STREAM.CREATE` and `STREAM.UPDATE`. Limits of
+/// `-1` and durations of zero mean unlimited.
+///
+/// ```rust
+/// extern crate nats_types;
+///
+/// use nats_types::{StorageType, StreamConfig};
+/// use std::time::Duration;
+///
+/// let config = StreamConfig {
+///     subjects: vec!["orders.>".to_string()],
+///     storage: StorageType::Memory,
+///     max_age: Duration::from_secs(3600),
+///     ..StreamConfig::new("ORDERS")
+/// };
+/// let json = serde_json::to_string(&config).unwrap();
+/// assert!(json.contains(r#""max_age":3600000000000"#));
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StreamConfig {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subjects: Vec<String>,
+    pub retention: RetentionPolicy,
+    #[serde(default = "unlimited")]
+    pub max_consumers: i64,
+    #[serde(default = "unlimited")]
+    pub max_msgs: i64,
+    #[serde(default = "unlimited")]
+    pub max_bytes: i64,
+    #[serde(default, with = "nanos")]
+    pub max_age: Duration,
+    #[serde(default = "unlimited")]
+    pub max_msgs_per_subject: i64,
+    #[serde(default = "unlimited")]
+    pub max_msg_size: i64,
+    pub discard: DiscardPolicy,
+    /// Applies `DiscardPolicy::New` to the per subject limit too
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub discard_new_per_subject: bool,
+    pub storage: StorageType,
+    #[serde(default = "one")]
+    pub num_replicas: usize,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub no_ack: bool,
+    /// The window in which messages with the same `Nats-Msg-Id` are discarded as duplicates.
+    /// Zero leaves the server's default in place.
+    #[serde(default, with = "nanos", skip_serializing_if = "is_zero_duration")]
+    pub duplicate_window: Duration,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub placement: Option<Placement>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror: Option<StreamSource>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<StreamSource>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub sealed: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub deny_delete: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub deny_purge: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub allow_rollup_hdrs: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub allow_direct: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub mirror_direct: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub republish: Option<Republish>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject_transform: Option<SubjectTransform>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<Compression>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub first_seq: u64,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+    /// Any fields not known to this crate, kept so that they survive a round trip
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+fn unlimited() -> i64 {
+    -1
+}
+
+fn one() -> usize {
+    1
+}
+
+impl StreamConfig {
+    /// A file-backed stream with the given name, no subjects and no limits, which discards its
+    /// oldest messages
+    pub fn new<S: Into<String>>(name: S) -> StreamConfig {
+        StreamConfig {
+            name: name.into(),
+            description: None,
+            subjects: Vec::new(),
+            retention: RetentionPolicy::Limits,
+            max_consumers: -1,
+            max_msgs: -1,
+            max_bytes: -1,
+            max_age: Duration::from_secs(0),
+            max_msgs_per_subject: -1,
+            max_msg_size: -1,
+            discard: DiscardPolicy::Old,
+            discard_new_per_subject: false,
+            storage: StorageType::File,
+            num_replicas: 1,
+            no_ack: false,
+            duplicate_window: Duration::from_secs(0),
+            placement: None,
+            mirror: None,
+            sources: Vec::new(),
+            sealed: false,
+            deny_delete: false,
+            deny_purge: false,
+            allow_rollup_hdrs: false,
+            allow_direct: false,
+            mirror_direct: false,
+            republish: None,
+            subject_transform: None,
+            compression: None,
+            first_seq: 0,
+            metadata: HashMap::new(),
+            extra: serde_json::Map::new(),
+        }
+    }
+}
+
+/// The state of the messages held by a stream
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StreamState {
+    pub messages: u64,
+    pub bytes: u64,
+    pub first_seq: u64,
+    /// An RFC 3339 timestamp
+    #[serde(default)]
+    pub first_ts: String,
+    pub last_seq: u64,
+    /// An RFC 3339 timestamp
+    #[serde(default)]
+    pub last_ts: String,
+    #[serde(default)]
+    pub num_subjects: u64,
+    /// The number of messages per subject, when requested with `subjects_filter`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub subjects: HashMap<String, u64>,
+    #[serde(default)]
+    pub num_deleted: u64,
+    /// The deleted sequences, when requested with `deleted_details`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deleted: Vec<u64>,
+    #[serde(default)]
+    pub consumer_count: usize,
+}
+
+/// A member of the group of servers holding a replicated stream or consumer
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PeerInfo {
+    pub name: String,
+    /// Indicates whether the peer is up to date with the leader
+    #[serde(default)]
+    pub current: bool,
+    #[serde(default)]
+    pub offline: bool,
+    /// The time since the peer was last seen
+    #[serde(default, with = "nanos")]
+    pub active: Duration,
+    /// The number of operations the peer is behind the leader by
+    #[serde(default)]
+    pub lag: u64,
+}
+
+/// The servers holding a replicated stream or consumer
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StreamClusterInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leader: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub replicas: Vec<PeerInfo>,
+}
+
+/// The progress of a mirror or source
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StreamSourceInfo {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external: Option<External>,
+    /// The number of messages the mirror or source is behind its origin by
+    pub lag: u64,
+    /// The time since the origin was last heard from. Zero if it never was.
+    #[serde(default, with = "nanos")]
+    pub active: Duration,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter_subject: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subject_transforms: Vec<SubjectTransform>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ApiError>,
+}
+
+/// The configuration and state of a stream, as returned by `STREAM.CREATE`, `STREAM.UPDATE`
+/// and `STREAM.INFO`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StreamInfo {
+    pub config: StreamConfig,
+    /// An RFC 3339 timestamp
+    pub created: String,
+    pub state: StreamState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<StreamClusterInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror: Option<StreamSourceInfo>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<StreamSourceInfo>,
+}
+
+/// The optional body of a `STREAM.INFO` request
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct StreamInfoRequest {
+    /// Includes the deleted sequences in the state
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub deleted_details: bool,
+    /// Includes the number of messages on each subject matching this filter in the state
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subjects_filter: Option<String>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub offset: usize,
+}
+
+/// The response to `STREAM.DELETE`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StreamDeleteResponse {
+    pub success: bool,
+}
+
+/// The optional body of a `STREAM.PURGE` request. Without one, every message is purged.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct StreamPurgeRequest {
+    /// Purges only the messages on subjects matching this filter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+    /// Purges the messages before this sequence
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+    /// Keeps this many of the newest messages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep: Option<u64>,
+}
+
+/// The response to `STREAM.PURGE`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StreamPurgeResponse {
+    pub success: bool,
+    pub purged: u64,
+}
+
+/// The optional body of a `STREAM.LIST` request, which is paged
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct StreamListRequest {
+    #[serde(default)]
+    pub offset: usize,
+    /// Lists only the streams that capture this subject
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+}
+
+/// A page of the response to `STREAM.LIST`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StreamListResponse {
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub streams: Vec<StreamInfo>,
+    /// Streams that could not be reached
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<String>,
+}
+
+/// The optional body of a `STREAM.NAMES` request, which is paged
+pub type StreamNamesRequest = StreamListRequest;
+
+/// A page of the response to `STREAM.NAMES`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StreamNamesResponse {
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub streams: Vec<String>,
+}
+
+impl JetStreamApi {
+    /// `STREAM.CREATE.<stream>`, taking a `StreamConfig` and returning a `StreamInfo`
+    pub fn stream_create(&self, stream: &str) -> Result<Subject, NatsParseError> {
+        self.named_subject("STREAM.CREATE", "stream", stream)
+    }
+
+    /// `STREAM.UPDATE.<stream>`, taking a `StreamConfig` and returning a `StreamInfo`
+    pub fn stream_update(&self, stream: &str) -> Result<Subject, NatsParseError> {
+        self.named_subject("STREAM.UPDATE", "stream", stream)
+    }
+
+    /// `STREAM.INFO.<stream>`, taking an optional `StreamInfoRequest` and returning a
+    /// `StreamInfo`
+    pub fn stream_info(&self, stream: &str) -> Result<Subject, NatsParseError> {
+        self.named_subject("STREAM.INFO", "stream", stream)
+    }
+
+    /// `STREAM.DELETE.<stream>`, taking no body and returning a `StreamDeleteResponse`
+    pub fn stream_delete(&self, stream: &str) -> Result<Subject, NatsParseError> {
+        self.named_subject("STREAM.DELETE", "stream", stream)
+    }
+
+    /// `STREAM.PURGE.<stream>`, taking an optional `StreamPurgeRequest` and returning a
+    /// `StreamPurgeResponse`
+    pub fn stream_purge(&self, stream: &str) -> Result<Subject, NatsParseError> {
+        self.named_subject("STREAM.PURGE", "stream", stream)
+    }
+
+    /// `STREAM.LIST`, taking an optional `StreamListRequest` and returning a
+    /// `StreamListResponse`
+    pub fn stream_list(&self) -> Subject {
+        self.subject("STREAM.LIST")
+    }
+
+    /// `STREAM.NAMES`, taking an optional `StreamNamesRequest` and returning a
+    /// `StreamNamesResponse`
+    pub fn stream_names(&self) -> Subject {
+        self.subject("STREAM.NAMES")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        Compression, DiscardPolicy, RetentionPolicy, StorageType, StreamConfig, StreamInfo,
+        StreamListResponse, StreamPurgeRequest,
+    };
+    use crate::parse_api_response;
+    use std::time::Duration;
+
+    // as sent by nats-server 2.10
+    const STREAM_INFO: &str = r#"{"type":"io.nats.jetstream.api.v1.stream_create_response","config":{"name":"ORDERS","subjects":["orders.>"],"retention":"workqueue","max_consumers":-1,"max_msgs":-1,"max_bytes":-1,"max_age":0,"max_msgs_per_subject":-1,"max_msg_size":-1,"discard":"old","storage":"file","num_replicas":3,"duplicate_window":120000000000,"compression":"s2","allow_direct":false,"mirror_direct":false,"sealed":false,"deny_delete":false,"deny_purge":false,"allow_rollup_hdrs":false,"consumer_limits":{},"subject_transform":{"src":"orders.>","dest":"orders.v2.>"},"sources":[{"name":"LEGACY","filter_subject":"old.>","external":{"api":"$JS.hub.API","deliver":""}}]},"created":"2024-01-02T03:04:05.123456789Z","state":{"messages":3,"bytes":120,"first_seq":1,"first_ts":"2024-01-02T03:04:05Z","last_seq":3,"last_ts":"2024-01-02T03:04:06Z","num_subjects":2,"consumer_count":1},"cluster":{"name":"east","leader":"n1","replicas":[{"name":"n2","current":true,"active":1500000,"lag":0},{"name":"n3","current":false,"offline":true,"active":0}]},"sources":[{"name":"LEGACY","lag":4,"active":-1,"error":{"code":503,"err_code":10000,"description":"source unavailable"}}],"ts":"2024-01-02T03:04:07Z"}"#;
+
+    #[test]
+    fn stream_info_response() {
+        let info: StreamInfo = parse_api_response(STREAM_INFO.as_bytes()).unwrap();
+        let config = &info.config;
+        assert_eq!(config.retention, RetentionPolicy::WorkQueue);
+        assert_eq!(config.storage, StorageType::File);
+        assert_eq!(config.num_replicas, 3);
+        assert_eq!(config.duplicate_window, Duration::from_secs(120));
+        assert_eq!(config.compression, Some(Compression::S2));
+        assert_eq!(
+            config.subject_transform.as_ref().unwrap().dest,
+            "orders.v2.>"
+        );
+        assert_eq!(
+            config.sources[0].external.as_ref().unwrap().api,
+            "$JS.hub.API"
+        );
+        assert!(config.extra.contains_key("consumer_limits"));
+        assert_eq!(info.state.messages, 3);
+        let cluster = info.cluster.as_ref().unwrap();
+        assert_eq!(cluster.replicas[0].active, Duration::from_micros(1500));
+        assert!(cluster.replicas[1].offline);
+        assert_eq!(info.sources[0].active, Duration::from_secs(0));
+        assert_eq!(info.sources[0].error.as_ref().unwrap().code, 503);
+
+        // survives a round trip
+        let json = serde_json::to_string(&info).unwrap();
+        let again: StreamInfo = serde_json::from_str(&json).unwrap();
+        assert_eq!(again, info);
+    }
+
+    #[test]
+    fn stream_config_request() {
+        let config = StreamConfig {
+            subjects: vec!["orders.>".to_string()],
+            discard: DiscardPolicy::New,
+            max_msgs: 1000,
+            ..StreamConfig::new("ORDERS")
+        };
+        let json: serde_json::Value = serde_json::to_value(&config).unwrap();
+        assert_eq!(json["name"], "ORDERS");
+        assert_eq!(json["retention"], "limits");
+        assert_eq!(json["discard"], "new");
+        assert_eq!(json["storage"], "file");
+        assert_eq!(json["max_msgs"], 1000);
+        assert_eq!(json["max_bytes"], -1);
+        assert_eq!(json["max_age"], 0);
+        assert!(json.get("duplicate_window").is_none());
+        assert!(json.get("sealed").is_none());
+        assert!(json.get("mirror").is_none());
+
+        let purge = StreamPurgeRequest {
+            filter: Some("orders.eu.>".to_string()),
+            keep: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_string(&purge).unwrap(),
+            r#"{"filter":"orders.eu.>","keep":10}"#
+        );
+    }
+
+    #[test]
+    fn stream_list_response() {
+        // unknown fields such as `type` and `ts` are ignored
+        let page = format!(
+            r#"{{"type":"io.nats.jetstream.api.v1.stream_list_response","total":1,"offset":0,"limit":256,"streams":[{}],"missing":["GONE"]}}"#,
+            STREAM_INFO
+        );
+        let page: StreamListResponse = parse_api_response(page.as_bytes()).unwrap();
+        assert_eq!(page.streams[0].config.name, "ORDERS");
+        assert_eq!(page.missing, ["GONE"]);
+    }
+}
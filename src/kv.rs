@@ -0,0 +1,477 @@
+use crate::{
+    Compression, DeliveredMessage, DiscardPolicy, HeaderDeliveredMessage, HeaderMap,
+    HeaderPublishMessage, JsMessageMetadata, NatsParseError, ParseErrorKind, Payload,
+    PublishMessage, PublishOptions, StorageType, StreamConfig, Subject, HEADER_ROLLUP,
+    ROLLUP_SUBJECT,
+};
+use std::time::{Duration, SystemTime};
+
+/// The header marking a message in a bucket as a delete or purge of its key
+pub const HEADER_KV_OPERATION: &str = "KV-Operation";
+
+const STREAM_PREFIX: &str = "KV_";
+const SUBJECT_PREFIX: &str = "$KV";
+const MAX_HISTORY: u8 = 64;
+// shortened to the ttl when that is shorter
+const DEFAULT_DUPLICATE_WINDOW: Duration = Duration::from_secs(120);
+
+/// The operation recorded by an entry in a bucket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KvOperation {
+    Put,
+    /// The key was deleted, keeping its history
+    Delete,
+    /// The key was deleted along with its history
+    Purge,
+}
+
+impl KvOperation {
+    fn parse(value: Option<&str>) -> Result<KvOperation, NatsParseError> {
+        match value {
+            None => Ok(KvOperation::Put),
+            Some("DEL") => Ok(KvOperation::Delete),
+            Some("PURGE") => Ok(KvOperation::Purge),
+            Some(other) => Err(NatsParseError::new(
+                ParseErrorKind::MalformedHeaders,
+                format!("Unknown {} '{}'", HEADER_KV_OPERATION, other),
+            )),
+        }
+    }
+}
+
+/// The value of a key at a given revision, as delivered by a consumer of the bucket's stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct KvEntry {
+    pub key: String,
+    /// Empty for deletes and purges
+    pub value: Payload,
+    /// The sequence of the entry in the bucket's stream
+    pub revision: u64,
+    pub operation: KvOperation,
+    pub created: SystemTime,
+}
+
+/// The configuration of a bucket, from which the configuration of its stream is derived
+#[derive(Debug, Clone, PartialEq)]
+pub struct KvConfig {
+    pub description: Option<String>,
+    /// The number of values kept for each key, from 1 to 64
+    pub history: u8,
+    /// How long values are kept, zero for ever
+    pub ttl: Duration,
+    /// The total size of the bucket, `-1` for unlimited
+    pub max_bytes: i64,
+    /// The size of the largest value, `-1` for unlimited
+    pub max_value_size: i64,
+    pub storage: StorageType,
+    pub replicas: usize,
+    pub compression: bool,
+}
+
+impl Default for KvConfig {
+    fn default() -> Self {
+        KvConfig {
+            description: None,
+            history: 1,
+            ttl: Duration::from_secs(0),
+            max_bytes: -1,
+            max_value_size: -1,
+            storage: StorageType::File,
+            replicas: 1,
+            compression: false,
+        }
+    }
+}
+
+/// The protocol of a key-value bucket, which is a stream named `KV_<bucket>` capturing the
+/// subjects `$KV.<bucket>.<key>`. The bucket produces the messages to publish for each
+/// operation and decodes the messages delivered by consumers of its stream into entries,
+/// leaving the sending and receiving of them to the caller.
+///
+/// ```rust
+/// extern crate nats_types;
+///
+/// use nats_types::{KvBucket, KvConfig};
+///
+/// let bucket = KvBucket::new("config").unwrap();
+/// let stream = bucket
+///     .stream_config(&KvConfig { history: 5, ..Default::default() })
+///     .unwrap();
+/// assert_eq!(stream.name, "KV_config");
+/// assert_eq!(stream.subjects, ["$KV.config.>"]);
+/// assert_eq!(stream.max_msgs_per_subject, 5);
+///
+/// let put = bucket.put("app.timeout", &b"30s"[..], None).unwrap();
+/// assert_eq!(put.subject, "$KV.config.app.timeout");
+///
+/// let update = bucket.update("app.timeout", &b"60s"[..], 3, None).unwrap();
+/// assert_eq!(update.headers.get("Nats-Expected-Last-Subject-Sequence"), Some("3"));
+///
+/// assert!(bucket.put("app timeout", &b"30s"[..], None).is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KvBucket {
+    name: String,
+    // includes the trailing '.'
+    prefix: String,
+}
+
+impl KvBucket {
+    /// A bucket with the given name, which may contain only letters, digits, `-` and `_`
+    pub fn new(name: &str) -> Result<KvBucket, NatsParseError> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(NatsParseError::new(
+                ParseErrorKind::InvalidSubject,
+                format!(
+                    "Invalid bucket name '{}' - names may contain only letters, digits, '-' and '_'",
+                    name
+                ),
+            ));
+        }
+        Ok(KvBucket {
+            name: name.to_string(),
+            prefix: format!("{}.{}.", SUBJECT_PREFIX, name),
+        })
+    }
+
+    /// The name of the bucket
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The name of the bucket's stream, `KV_<bucket>`
+    pub fn stream_name(&self) -> String {
+        format!("{}{}", STREAM_PREFIX, self.name)
+    }
+
+    /// The subject of the given key, `$KV.<bucket>.<key>`. Keys may contain letters, digits,
+    /// `-`, `/`, `_`, `=` and `.`, but may not begin or end with `.`.
+    pub fn key_subject(&self, key: &str) -> Result<Subject, NatsParseError> {
+        let valid = !key.starts_with('.')
+            && !key.ends_with('.')
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-/_=.".contains(c));
+        if !valid {
+            return Err(NatsParseError::new(
+                ParseErrorKind::InvalidSubject,
+                format!("Invalid key '{}'", key),
+            ));
+        }
+        Subject::literal(format!("{}{}", self.prefix, key))
+    }
+
+    /// The configuration of the bucket's stream. Fails if the history is not between 1 and 64.
+    pub fn stream_config(&self, config: &KvConfig) -> Result<StreamConfig, NatsParseError> {
+        if config.history < 1 || config.history > MAX_HISTORY {
+            return Err(NatsParseError::new(
+                ParseErrorKind::InvalidConfig,
+                format!(
+                    "Invalid history of {} - buckets keep between 1 and {} entries per key",
+                    config.history, MAX_HISTORY
+                ),
+            ));
+        }
+        let duplicate_window = if config.ttl > Duration::from_secs(0) {
+            config.ttl.min(DEFAULT_DUPLICATE_WINDOW)
+        } else {
+            DEFAULT_DUPLICATE_WINDOW
+        };
+        Ok(StreamConfig {
+            description: config.description.clone(),
+            subjects: vec![format!("{}>", self.prefix)],
+            max_msgs_per_subject: i64::from(config.history),
+            max_bytes: config.max_bytes,
+            max_age: config.ttl,
+            max_msg_size: config.max_value_size,
+            storage: config.storage,
+            num_replicas: config.replicas,
+            discard: DiscardPolicy::New,
+            duplicate_window,
+            allow_rollup_hdrs: true,
+            deny_delete: true,
+            allow_direct: true,
+            compression: if config.compression {
+                Some(Compression::S2)
+            } else {
+                None
+            },
+            ..StreamConfig::new(self.stream_name())
+        })
+    }
+
+    /// Sets the value of the key
+    pub fn put(
+        &self,
+        key: &str,
+        value: impl Into<Payload>,
+        reply_to: Option<String>,
+    ) -> Result<PublishMessage, NatsParseError> {
+        PublishMessage::new(self.key_subject(key)?, reply_to, value)
+    }
+
+    /// Sets the value of the key, which the stream rejects if the key has a value. A key whose
+    /// last entry is a delete or purge must be recreated with `update` instead.
+    pub fn create(
+        &self,
+        key: &str,
+        value: impl Into<Payload>,
+        reply_to: Option<String>,
+    ) -> Result<HeaderPublishMessage, NatsParseError> {
+        self.update(key, value, 0, reply_to)
+    }
+
+    /// Sets the value of the key, which the stream rejects unless the key's last entry is at
+    /// the given revision
+    pub fn update(
+        &self,
+        key: &str,
+        value: impl Into<Payload>,
+        revision: u64,
+        reply_to: Option<String>,
+    ) -> Result<HeaderPublishMessage, NatsParseError> {
+        let options = PublishOptions {
+            expected_last_subject_sequence: Some(revision),
+            ..Default::default()
+        };
+        options.to_publish(self.key_subject(key)?, reply_to, value)
+    }
+
+    /// Deletes the key, keeping its history
+    pub fn delete(
+        &self,
+        key: &str,
+        reply_to: Option<String>,
+    ) -> Result<HeaderPublishMessage, NatsParseError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(HEADER_KV_OPERATION, "DEL")?;
+        HeaderPublishMessage::new(self.key_subject(key)?, reply_to, headers, Payload::new())
+    }
+
+    /// Deletes the key along with its history, by rolling up the subject into a single entry
+    pub fn purge(
+        &self,
+        key: &str,
+        reply_to: Option<String>,
+    ) -> Result<HeaderPublishMessage, NatsParseError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(HEADER_KV_OPERATION, "PURGE")?;
+        headers.insert(HEADER_ROLLUP, ROLLUP_SUBJECT)?;
+        HeaderPublishMessage::new(self.key_subject(key)?, reply_to, headers, Payload::new())
+    }
+
+    /// Decodes a message delivered by a consumer of the bucket's stream
+    pub fn decode_entry(&self, msg: &DeliveredMessage) -> Result<KvEntry, NatsParseError> {
+        self.entry(&msg.subject, msg.jetstream_metadata()?, None, &msg.payload)
+    }
+
+    /// Decodes a message with headers delivered by a consumer of the bucket's stream
+    pub fn decode_header_entry(
+        &self,
+        msg: &HeaderDeliveredMessage,
+    ) -> Result<KvEntry, NatsParseError> {
+        self.entry(
+            &msg.subject,
+            msg.jetstream_metadata()?,
+            msg.headers.get(HEADER_KV_OPERATION),
+            &msg.payload,
+        )
+    }
+
+    fn entry(
+        &self,
+        subject: &Subject,
+        metadata: JsMessageMetadata,
+        operation: Option<&str>,
+        payload: &Payload,
+    ) -> Result<KvEntry, NatsParseError> {
+        let key = subject
+            .as_str()
+            .strip_prefix(self.prefix.as_str())
+            .ok_or_else(|| {
+                NatsParseError::new(
+                    ParseErrorKind::InvalidSubject,
+                    format!(
+                        "Subject '{}' is not a key of bucket '{}'",
+                        subject, self.name
+                    ),
+                )
+            })?;
+        Ok(KvEntry {
+            key: key.to_string(),
+            value: payload.clone(),
+            revision: metadata.stream_sequence,
+            operation: KvOperation::parse(operation)?,
+            created: metadata.timestamp,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{KvBucket, KvConfig, KvOperation};
+    use crate::{DeliveredMessage, HeaderDeliveredMessage, HeaderMap, ParseErrorKind};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    const REPLY: &str = "$JS.ACK.KV_config.watch.1.12.3.1704164645000000000.0";
+
+    #[test]
+    fn bucket_names_and_keys() {
+        assert!(KvBucket::new("my-config_2").is_ok());
+        for bad in &["", "my.config", "my config", "conf*"] {
+            assert!(KvBucket::new(bad).is_err(), "{}", bad);
+        }
+
+        let bucket = KvBucket::new("config").unwrap();
+        assert_eq!(bucket.stream_name(), "KV_config");
+        assert_eq!(
+            bucket.key_subject("a/b=c.d_e-f").unwrap(),
+            "$KV.config.a/b=c.d_e-f"
+        );
+        for bad in &["", ".a", "a.", "a..b", "a*", "a>", "a b"] {
+            let err = bucket.key_subject(bad).unwrap_err();
+            assert_eq!(err.kind(), ParseErrorKind::InvalidSubject, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn bucket_stream_config() {
+        let bucket = KvBucket::new("config").unwrap();
+        let config = bucket
+            .stream_config(&KvConfig {
+                history: 64,
+                ttl: Duration::from_secs(30),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(config.max_msgs_per_subject, 64);
+        assert_eq!(config.max_age, Duration::from_secs(30));
+        assert_eq!(config.duplicate_window, Duration::from_secs(30));
+        assert!(config.allow_rollup_hdrs && config.deny_delete && config.allow_direct);
+
+        let config = bucket.stream_config(&KvConfig::default()).unwrap();
+        assert_eq!(config.max_msgs_per_subject, 1);
+        assert_eq!(config.duplicate_window, Duration::from_secs(120));
+
+        for history in &[0, 65] {
+            let err = bucket
+                .stream_config(&KvConfig {
+                    history: *history,
+                    ..Default::default()
+                })
+                .unwrap_err();
+            assert_eq!(err.kind(), ParseErrorKind::InvalidConfig, "{}", history);
+        }
+    }
+
+    #[test]
+    fn bucket_publishes() {
+        let bucket = KvBucket::new("config").unwrap();
+        let create = bucket
+            .create("timeout", &b"30s"[..], Some("_INBOX.1".to_string()))
+            .unwrap();
+        assert_eq!(
+            create.headers.get("Nats-Expected-Last-Subject-Sequence"),
+            Some("0")
+        );
+        assert_eq!(create.reply_to.as_deref(), Some("_INBOX.1"));
+
+        let delete = bucket.delete("timeout", None).unwrap();
+        assert_eq!(delete.headers.get("KV-Operation"), Some("DEL"));
+        assert!(delete.payload.is_empty());
+
+        let purge = bucket.purge("timeout", None).unwrap();
+        assert_eq!(
+            purge.headers.to_string(),
+            "NATS/1.0\r\nKV-Operation: PURGE\r\nNats-Rollup: sub\r\n\r\n"
+        );
+        assert_eq!(purge.total_size, purge.header_size);
+    }
+
+    #[test]
+    fn bucket_recreate_after_delete() {
+        let bucket = KvBucket::new("config").unwrap();
+        for (operation, expected) in &[("DEL", KvOperation::Delete), ("PURGE", KvOperation::Purge)]
+        {
+            let mut headers = HeaderMap::new();
+            headers.insert("KV-Operation", *operation).unwrap();
+            let deleted = HeaderDeliveredMessage::new(
+                "$KV.config.timeout".parse().unwrap(),
+                1,
+                Some(REPLY.to_string()),
+                headers,
+                &b""[..],
+            );
+            let entry = bucket.decode_header_entry(&deleted).unwrap();
+            assert_eq!(entry.operation, *expected);
+
+            // create expects no entry at all, which the stream would reject as the delete or
+            // purge is the key's last entry, so the key is recreated at the revision of that entry
+            let create = bucket.create(&entry.key, &b"30s"[..], None).unwrap();
+            assert_eq!(
+                create.headers.get("Nats-Expected-Last-Subject-Sequence"),
+                Some("0")
+            );
+            let recreate = bucket
+                .update(&entry.key, &b"30s"[..], entry.revision, None)
+                .unwrap();
+            assert_eq!(
+                recreate.headers.get("Nats-Expected-Last-Subject-Sequence"),
+                Some("12")
+            );
+            assert_eq!(recreate.subject, create.subject);
+            assert_eq!(recreate.payload, create.payload);
+        }
+    }
+
+    #[test]
+    fn bucket_entries() {
+        let bucket = KvBucket::new("config").unwrap();
+        let put = DeliveredMessage::new(
+            "$KV.config.app.timeout".parse().unwrap(),
+            1,
+            Some(REPLY.to_string()),
+            &b"30s"[..],
+        );
+        let entry = bucket.decode_entry(&put).unwrap();
+        assert_eq!(entry.key, "app.timeout");
+        assert_eq!(entry.value, &b"30s"[..]);
+        assert_eq!(entry.revision, 12);
+        assert_eq!(entry.operation, KvOperation::Put);
+        assert_eq!(
+            entry.created,
+            UNIX_EPOCH + Duration::from_secs(1_704_164_645)
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert("KV-Operation", "DEL").unwrap();
+        let delete = HeaderDeliveredMessage::new(
+            "$KV.config.app.timeout".parse().unwrap(),
+            1,
+            Some(REPLY.to_string()),
+            headers,
+            &b""[..],
+        );
+        let entry = bucket.decode_header_entry(&delete).unwrap();
+        assert_eq!(entry.operation, KvOperation::Delete);
+
+        let other = DeliveredMessage::new(
+            "$KV.other.app.timeout".parse().unwrap(),
+            1,
+            Some(REPLY.to_string()),
+            &b"30s"[..],
+        );
+        assert!(bucket.decode_entry(&other).is_err());
+        let unacked = DeliveredMessage::new(
+            "$KV.config.app.timeout".parse().unwrap(),
+            1,
+            None,
+            &b"30s"[..],
+        );
+        assert!(bucket.decode_entry(&unacked).is_err());
+    }
+}
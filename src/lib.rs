@@ -15,15 +15,18 @@
 //!
 //! let publish = ProtocolMessage::Publish( PublishMessage {
 //!     reply_to: Some("INBOX.42".to_string()),
-//!     subject: "workdispatch".to_string(),
+//!     subject: "workdispatch".parse().unwrap(),
 //!     payload_size: 11,
-//!     payload: b"Hello World".to_vec(),
+//!     payload: b"Hello World"[..].into(),
 //! });
 //!
 //! let out = format!("{}", publish);
 //! assert_eq!(out, "PUB workdispatch INBOX.42 11\r\nHello World\r\n");
 //! ```
 //!
+//! Formatting is lossy for payloads that are not valid UTF-8, which are written as
+//! `<<BAD PAYLOAD>>`. Use `Encode` to produce the exact bytes to send.
+//!
 //! The same message can be constructed from the 2-line message received from a NATS server:
 //! ```rust
 //! extern crate nats_types;
@@ -37,7 +40,7 @@
 //!     assert_eq!(pubm.payload_size, 11);
 //!     assert_eq!(pubm.subject, "FOO");
 //!     assert_eq!(pubm.reply_to, None);
-//!     assert_eq!(pubm.payload, b"Hello NATS!");
+//!     assert_eq!(pubm.payload[..], b"Hello NATS!"[..]);
 //! }
 //! ```
 #[macro_use]
@@ -49,14 +52,56 @@ extern crate serde_json;
 extern crate nom;
 
 use nom::AsBytes;
-use std::error::Error;
-use std::fmt;
 use std::fmt::Display;
 use std::fmt::Formatter;
-use std::io::Write;
 use std::str::FromStr;
 
-pub use parser::parse_msg_header; 
+pub use borrowed::{DeliveredMessageRef, HeaderDeliveredMessageRef, ProtocolMessageRef};
+#[cfg(feature = "tokio-codec")]
+pub use codec::NatsCodec;
+#[cfg(feature = "nkeys")]
+pub use creds::{
+    Credentials, CredentialsError, Permission, ResponsePermission, UserClaims, UserJwt,
+    UserPermissionLimits,
+};
+pub use decoder::{ProtocolDecoder, DEFAULT_MAX_CONTROL_LINE, DEFAULT_MAX_PAYLOAD};
+pub use encode::Encode;
+pub use error::{NatsParseError, ParseErrorKind};
+pub use header::{
+    HeaderMap, STATUS_IDLE_HEARTBEAT, STATUS_NOT_FOUND, STATUS_NO_RESPONDERS,
+    STATUS_REQUEST_TIMEOUT,
+};
+pub use inbox::{new_inbox, ResponseInbox, INBOX_PREFIX};
+pub use jetstream::{
+    parse_api_response, AckKind, AckPolicy, ApiError, Compression, ConsumerAction, ConsumerConfig,
+    ConsumerCreateRequest, ConsumerDeleteResponse, ConsumerInfo, ConsumerListRequest,
+    ConsumerListResponse, ConsumerNamesRequest, ConsumerNamesResponse, DeliverPolicy,
+    DiscardPolicy, External, JetStreamApi, JetStreamError, JsMessageMetadata, PeerInfo, Placement,
+    PubAck, PublishOptions, PullRequest, ReplayPolicy, Republish, RetentionPolicy, SequenceInfo,
+    StorageType, StreamClusterInfo, StreamConfig, StreamDeleteResponse, StreamInfo,
+    StreamInfoRequest, StreamListRequest, StreamListResponse, StreamNamesRequest,
+    StreamNamesResponse, StreamPurgeRequest, StreamPurgeResponse, StreamSource, StreamSourceInfo,
+    StreamState, SubjectTransform, ACK_PREFIX, DEFAULT_API_PREFIX, HEADER_EXPECTED_LAST_MSG_ID,
+    HEADER_EXPECTED_LAST_SEQUENCE, HEADER_EXPECTED_LAST_SUBJECT_SEQUENCE, HEADER_EXPECTED_STREAM,
+    HEADER_MSG_ID, HEADER_ROLLUP, ROLLUP_ALL, ROLLUP_SUBJECT,
+};
+pub use kv::{KvBucket, KvConfig, KvEntry, KvOperation, HEADER_KV_OPERATION};
+#[cfg(feature = "nkeys")]
+pub use nkeys::{verify_signature, KeyKind, KeyPair, NkeyError};
+pub use nuid::{next_nuid, Nuid, NUID_LEN};
+pub use parser::parse_msg_header;
+pub use server_error::{ServerError, ServerErrorKind};
+pub use subject::{Subject, Tokens};
+pub use sublist::{Matches, Sublist};
+
+/// The type of message payloads. With the `bytes` feature this is `bytes::Bytes`, making clones
+/// cheap and allowing `NatsCodec` to slice payloads out of its read buffer without copying them.
+#[cfg(not(feature = "bytes"))]
+pub type Payload = Vec<u8>;
+/// The type of message payloads. With the `bytes` feature this is `bytes::Bytes`, making clones
+/// cheap and allowing `NatsCodec` to slice payloads out of its read buffer without copying them.
+#[cfg(feature = "bytes")]
+pub type Payload = bytes::Bytes;
 
 /// An enum whose variants are all of the available protocol messages as defined by the
 /// NATS protocol documentation.
@@ -64,88 +109,118 @@ pub use parser::parse_msg_header;
 pub enum ProtocolMessage {
     Unsubscribe(UnsubscribeMessage),
     Publish(PublishMessage),
+    HeaderPublish(HeaderPublishMessage),
     Message(DeliveredMessage),
+    HeaderMessage(HeaderDeliveredMessage),
     Subscribe(SubscribeMessage),
     Ping,
     Pong,
     Ok,
-    Error(String),
+    Error(ServerError),
     Info(ServerInformation),
     Connect(ConnectionInformation),
 }
 
-impl Display for ProtocolMessage {
-    fn fmt(&self, f: &mut Formatter) -> Result<(), ::std::fmt::Error> {
-        match self {
-            ProtocolMessage::Unsubscribe(m) => write!(f, "{}", m),
-            ProtocolMessage::Subscribe(m) => write!(f, "{}", m),
-            ProtocolMessage::Publish(m) => write!(f, "{}", m),
-            ProtocolMessage::Message(m) => write!(f, "{}", m),
-            ProtocolMessage::Ping => write!(f, "PING\r\n"),
-            ProtocolMessage::Pong => write!(f, "PONG\r\n"),
-            ProtocolMessage::Ok => write!(f, "+OK\r\n"),
-            ProtocolMessage::Error(s) => write!(f, "-ERR '{}'", s),
-            ProtocolMessage::Info(si) => write!(f, "{}", si),
-            ProtocolMessage::Connect(ci) => write!(f, "{}", ci),
-        }
-    }
-}
-
-impl FromStr for ProtocolMessage {
-    type Err = NatsParseError;
-
-    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
-        if s.starts_with("UNSUB") {
-            match UnsubscribeMessage::from_str(s) {
+impl ProtocolMessage {
+    /// Parses a single protocol message from raw bytes. Unlike `from_str`, this does not
+    /// require the payload of a `PUB` or `MSG` to be valid UTF-8.
+    pub fn parse(buf: &[u8]) -> Result<ProtocolMessage, NatsParseError> {
+        if buf.starts_with(b"UNSUB") {
+            match UnsubscribeMessage::parse(buf) {
                 Ok(m) => Ok(ProtocolMessage::Unsubscribe(m)),
                 Err(e) => Err(e),
             }
-        } else if s.starts_with("PUB") {
-            match PublishMessage::from_str(s) {
+        } else if buf.starts_with(b"HPUB") {
+            match HeaderPublishMessage::parse(buf) {
+                Ok(m) => Ok(ProtocolMessage::HeaderPublish(m)),
+                Err(e) => Err(e),
+            }
+        } else if buf.starts_with(b"HMSG") {
+            match HeaderDeliveredMessage::parse(buf) {
+                Ok(m) => Ok(ProtocolMessage::HeaderMessage(m)),
+                Err(e) => Err(e),
+            }
+        } else if buf.starts_with(b"PUB") {
+            match PublishMessage::parse(buf) {
                 Ok(m) => Ok(ProtocolMessage::Publish(m)),
                 Err(e) => Err(e),
             }
-        } else if s.starts_with("MSG") {
-            match DeliveredMessage::from_str(s) {
+        } else if buf.starts_with(b"MSG") {
+            match DeliveredMessage::parse(buf) {
                 Ok(m) => Ok(ProtocolMessage::Message(m)),
                 Err(e) => Err(e),
             }
-        } else if s.starts_with("SUB") {
-            match SubscribeMessage::from_str(s) {
+        } else if buf.starts_with(b"SUB") {
+            match SubscribeMessage::parse(buf) {
                 Ok(m) => Ok(ProtocolMessage::Subscribe(m)),
                 Err(e) => Err(e),
             }
-        } else if s.starts_with("PING") {
+        } else if buf.starts_with(b"PING") {
             Ok(ProtocolMessage::Ping)
-        } else if s.starts_with("PONG") {
+        } else if buf.starts_with(b"PONG") {
             Ok(ProtocolMessage::Pong)
-        } else if s.starts_with("+OK") {
+        } else if buf.starts_with(b"+OK") {
             Ok(ProtocolMessage::Ok)
-        } else if s.starts_with("-ERR") {
-            match parser::parse_err_header(s) {
-                Some(h) => Ok(ProtocolMessage::Error(h.message)),
-                None => Err(NatsParseError {
-                    msg: "Failed to parse protocol message of type ERR".to_string(),
-                }),
-            }
-        } else if s.starts_with("INFO") {
-            match ServerInformation::from_str(s) {
+        } else if buf.starts_with(b"-ERR") {
+            ServerError::parse(buf).map(ProtocolMessage::Error)
+        } else if buf.starts_with(b"INFO") {
+            match ServerInformation::parse(buf) {
                 Ok(m) => Ok(ProtocolMessage::Info(m)),
                 Err(e) => Err(e),
             }
-        } else if s.starts_with("CONNECT") {
-            match ConnectionInformation::from_str(s) {
+        } else if buf.starts_with(b"CONNECT") {
+            match ConnectionInformation::parse(buf) {
                 Ok(m) => Ok(ProtocolMessage::Connect(m)),
                 Err(e) => Err(e),
             }
         } else {
-            Err(NatsParseError {
-                msg: "Failed to parse protocol message - unknown message type?".to_string(),
-            })
+            let verb: Vec<u8> = buf
+                .iter()
+                .take_while(|c| !c.is_ascii_whitespace())
+                .take(32)
+                .cloned()
+                .collect();
+            let verb = String::from_utf8_lossy(&verb);
+            Err(NatsParseError::new(
+                ParseErrorKind::UnknownOperation,
+                format!(
+                    "Failed to parse protocol message - unknown operation '{}'",
+                    verb
+                ),
+            )
+            .with_verb(verb))
         }
     }
 }
 
+impl Display for ProtocolMessage {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), ::std::fmt::Error> {
+        match self {
+            ProtocolMessage::Unsubscribe(m) => write!(f, "{}", m),
+            ProtocolMessage::Subscribe(m) => write!(f, "{}", m),
+            ProtocolMessage::Publish(m) => write!(f, "{}", m),
+            ProtocolMessage::HeaderPublish(m) => write!(f, "{}", m),
+            ProtocolMessage::Message(m) => write!(f, "{}", m),
+            ProtocolMessage::HeaderMessage(m) => write!(f, "{}", m),
+            ProtocolMessage::Ping => write!(f, "PING\r\n"),
+            ProtocolMessage::Pong => write!(f, "PONG\r\n"),
+            ProtocolMessage::Ok => write!(f, "+OK\r\n"),
+            // written without a line terminator, which only `Encode` adds
+            ProtocolMessage::Error(s) => write!(f, "-ERR '{}'", s),
+            ProtocolMessage::Info(si) => write!(f, "{}", si),
+            ProtocolMessage::Connect(ci) => write!(f, "{}", ci),
+        }
+    }
+}
+
+impl FromStr for ProtocolMessage {
+    type Err = NatsParseError;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        ProtocolMessage::parse(s.as_bytes())
+    }
+}
+
 /// Represents server connection information sent by the client to configure the connection
 /// immediately after connecting. The NATS protocol definition for this is as follows:
 /// ```text
@@ -167,14 +242,33 @@ pub struct ConnectionInformation {
     pub version: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub protocol: Option<u64>,
+    /// Indicates whether the server should deliver messages published by this connection back
+    /// to its own subscriptions. The server's default of `true` is not sent.
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub echo: bool,
+    /// Opts into `HPUB` and `HMSG`
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub headers: bool,
+    /// Asks the server to answer requests that have no subscribers with a 503 status message.
+    /// Requires `headers`.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub no_responders: bool,
+    /// The public nkey of the client, sent along with the `sig` of the server's nonce
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nkey: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sig: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub jwt: Option<String>,
+    /// Any fields not known to this crate, kept so that they survive a round trip
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 impl ConnectionInformation {
-    /// Constructor to create a new connection information struct
+    /// Constructor to create a new connection information struct. Newer fields are left at
+    /// their defaults; `ConnectionInformation::builder` is usually more convenient.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         verbose: bool,
         pedantic: bool,
@@ -200,12 +294,173 @@ impl ConnectionInformation {
             name,
             version,
             protocol,
+            echo: true,
+            headers: false,
+            no_responders: false,
+            nkey: None,
             sig,
             jwt,
+            extra: serde_json::Map::new(),
+        }
+    }
+
+    /// Starts building a connection information struct for a client written in Rust,
+    /// speaking protocol version 1
+    pub fn builder() -> ConnectionInformationBuilder {
+        ConnectionInformationBuilder::default()
+    }
+
+    /// Parses a `CONNECT` message from raw bytes
+    pub fn parse(buf: &[u8]) -> Result<ConnectionInformation, NatsParseError> {
+        let (offset, json) = parser::json_body(buf, b"CONNECT");
+        serde_json::from_slice(json).map_err(|e| NatsParseError::json("CONNECT", offset, e))
+    }
+}
+
+/// A builder for `ConnectionInformation`, obtained from `ConnectionInformation::builder`
+///
+/// ```rust
+/// extern crate nats_types;
+///
+/// use nats_types::ConnectionInformation;
+///
+/// let connect = ConnectionInformation::builder()
+///     .name("worker")
+///     .version("0.3.0")
+///     .headers(true)
+///     .no_responders(true)
+///     .build();
+/// assert!(connect.to_string().contains(r#""headers":true,"no_responders":true"#));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConnectionInformationBuilder {
+    info: ConnectionInformation,
+}
+
+impl Default for ConnectionInformationBuilder {
+    fn default() -> Self {
+        ConnectionInformationBuilder {
+            info: ConnectionInformation::new(
+                false,
+                false,
+                false,
+                None,
+                None,
+                None,
+                "rust".to_string(),
+                String::new(),
+                String::new(),
+                Some(1),
+                None,
+                None,
+            ),
         }
     }
 }
 
+impl ConnectionInformationBuilder {
+    /// Requests `+OK` acknowledgements for each protocol message
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.info.verbose = verbose;
+        self
+    }
+
+    /// Requests additional strict format checking by the server
+    pub fn pedantic(mut self, pedantic: bool) -> Self {
+        self.info.pedantic = pedantic;
+        self
+    }
+
+    /// Indicates whether the client requires a TLS connection
+    pub fn tls_required(mut self, tls_required: bool) -> Self {
+        self.info.tls_required = tls_required;
+        self
+    }
+
+    /// Authenticates with a token
+    pub fn auth_token<S: Into<String>>(mut self, token: S) -> Self {
+        self.info.auth_token = Some(token.into());
+        self
+    }
+
+    /// Authenticates with a user name and password
+    pub fn user_and_pass<U: Into<String>, P: Into<String>>(mut self, user: U, pass: P) -> Self {
+        self.info.user = Some(user.into());
+        self.info.pass = Some(pass.into());
+        self
+    }
+
+    /// The implementation language of the client
+    pub fn lang<S: Into<String>>(mut self, lang: S) -> Self {
+        self.info.lang = lang.into();
+        self
+    }
+
+    /// The optional name of the client
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.info.name = name.into();
+        self
+    }
+
+    /// The version of the client
+    pub fn version<S: Into<String>>(mut self, version: S) -> Self {
+        self.info.version = version.into();
+        self
+    }
+
+    /// The protocol version spoken by the client, or `None` to omit it
+    pub fn protocol(mut self, protocol: Option<u64>) -> Self {
+        self.info.protocol = protocol;
+        self
+    }
+
+    /// Whether the connection receives its own messages
+    pub fn echo(mut self, echo: bool) -> Self {
+        self.info.echo = echo;
+        self
+    }
+
+    /// Opts into `HPUB` and `HMSG`
+    pub fn headers(mut self, headers: bool) -> Self {
+        self.info.headers = headers;
+        self
+    }
+
+    /// Opts into no responders status messages, also enabling headers
+    pub fn no_responders(mut self, no_responders: bool) -> Self {
+        self.info.no_responders = no_responders;
+        if no_responders {
+            self.info.headers = true;
+        }
+        self
+    }
+
+    /// Authenticates with an nkey, given the public key and the signature of the server's nonce
+    pub fn nkey<K: Into<String>, S: Into<String>>(mut self, nkey: K, sig: S) -> Self {
+        self.info.nkey = Some(nkey.into());
+        self.info.sig = Some(sig.into());
+        self
+    }
+
+    /// Authenticates with a user JWT, given the JWT and the signature of the server's nonce
+    pub fn jwt<J: Into<String>, S: Into<String>>(mut self, jwt: J, sig: S) -> Self {
+        self.info.jwt = Some(jwt.into());
+        self.info.sig = Some(sig.into());
+        self
+    }
+
+    /// Adds a field not otherwise supported by this crate
+    pub fn extra<S: Into<String>>(mut self, key: S, value: serde_json::Value) -> Self {
+        self.info.extra.insert(key.into(), value);
+        self
+    }
+
+    /// Finishes building the connection information
+    pub fn build(self) -> ConnectionInformation {
+        self.info
+    }
+}
+
 impl Display for ConnectionInformation {
     fn fmt(&self, f: &mut Formatter) -> Result<(), ::std::fmt::Error> {
         let out = serde_json::to_string(self);
@@ -220,14 +475,7 @@ impl FromStr for ConnectionInformation {
     type Err = NatsParseError;
 
     fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
-        let s = s.replace("CONNECT ", "");
-        println!("{}", s);
-        match serde_json::from_str(s.trim()) {
-            Ok(ci) => Ok(ci),
-            Err(e) => Err(NatsParseError {
-                msg: format!("Failed to parse connection info JSON: {}", e),
-            }),
-        }
+        ConnectionInformation::parse(s.as_bytes())
     }
 }
 
@@ -239,34 +487,75 @@ impl FromStr for ConnectionInformation {
 #[derive(Serialize, Debug, Clone, PartialEq, Deserialize)]
 pub struct ServerInformation {
     pub server_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_name: Option<String>,
     pub version: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub proto: Option<usize>,
-    pub go: String,
-    pub host: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_commit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub go: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
     pub port: u64,
+    /// Indicates whether the server supports `HPUB` and `HMSG`
+    #[serde(default)]
+    pub headers: bool,
+    /// Indicates whether the client must authenticate in its `CONNECT`. When present, the
+    /// `nonce` is to be signed with the client's nkey.
     #[serde(default)]
     pub auth_required: bool,
     #[serde(default)]
     pub tls_required: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub tls_verify: bool,
+    /// Indicates the server accepts, but does not require, a TLS upgrade
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub tls_available: bool,
     #[serde(default)]
     pub max_payload: u64,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub jetstream: bool,
+    /// The public curve key used to encrypt auth callout requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xkey: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub client_id: Option<usize>,
+    /// The address at which the server sees this client
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<String>,
+    /// The JetStream domain of the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub connect_urls: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ws_connect_urls: Option<Vec<String>>,
+    /// Indicates the server is in lame duck mode and clients should reconnect elsewhere
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub ldm: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub nonce: Option<String>,
+    /// Any fields not known to this crate, kept so that they survive a round trip
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 impl ServerInformation {
-    /// Constructor to create a new server information
+    /// Constructor to create a new server information. Fields introduced by newer servers are
+    /// left at their defaults and may be set on the returned value.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         server_id: String,
         version: String,
         proto: Option<usize>,
-        go: String,
-        host: String,
+        go: Option<String>,
+        host: Option<String>,
         port: u64,
         auth_required: bool,
         tls_required: bool,
@@ -277,19 +566,39 @@ impl ServerInformation {
     ) -> ServerInformation {
         ServerInformation {
             server_id,
+            server_name: None,
             version,
             proto,
+            git_commit: None,
             go,
             host,
             port,
+            headers: false,
             auth_required,
             tls_required,
+            tls_verify: false,
+            tls_available: false,
             max_payload,
+            jetstream: false,
+            xkey: None,
             client_id,
+            client_ip: None,
+            ip: None,
+            cluster: None,
+            domain: None,
             connect_urls,
+            ws_connect_urls: None,
+            ldm: false,
             nonce,
+            extra: serde_json::Map::new(),
         }
     }
+
+    /// Parses an `INFO` message from raw bytes
+    pub fn parse(buf: &[u8]) -> Result<ServerInformation, NatsParseError> {
+        let (offset, json) = parser::json_body(buf, b"INFO");
+        serde_json::from_slice(json).map_err(|e| NatsParseError::json("INFO", offset, e))
+    }
 }
 
 impl Display for ServerInformation {
@@ -306,13 +615,7 @@ impl FromStr for ServerInformation {
     type Err = NatsParseError;
 
     fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
-        let s = s.replace("INFO ", "");
-        match serde_json::from_str(s.trim()) {
-            Ok(si) => Ok(si),
-            Err(_) => Err(NatsParseError {
-                msg: "Failed to parse server info JSON".to_string(),
-            }),
-        }
+        ServerInformation::parse(s.as_bytes())
     }
 }
 
@@ -322,21 +625,22 @@ impl FromStr for ServerInformation {
 /// ```
 #[derive(Debug, Clone, PartialEq)]
 pub struct DeliveredMessage {
-    pub subject: String,
+    pub subject: Subject,
     pub subscription_id: usize,
     pub reply_to: Option<String>,
     pub payload_size: usize,
-    pub payload: Vec<u8>,
+    pub payload: Payload,
 }
 
 impl DeliveredMessage {
     /// Constructor to build a new message from a given subject, payload, etc
     pub fn new(
-        subject: String,
+        subject: Subject,
         subscription_id: usize,
         reply_to: Option<String>,
-        payload: Vec<u8>,
+        payload: impl Into<Payload>,
     ) -> DeliveredMessage {
+        let payload = payload.into();
         DeliveredMessage {
             subject,
             subscription_id,
@@ -345,6 +649,28 @@ impl DeliveredMessage {
             payload,
         }
     }
+
+    /// Single-allocation conversion from source data to a byte vector suitable for transmission
+    pub fn as_vec(
+        subject: &Subject,
+        subscription_id: usize,
+        reply_to: Option<&str>,
+        payload: &[u8],
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut buffer = Vec::with_capacity(encode::msg_len(
+            subject,
+            subscription_id,
+            reply_to,
+            payload.len(),
+        ));
+        encode::write_msg(&mut buffer, subject, subscription_id, reply_to, payload)?;
+        Ok(buffer)
+    }
+
+    /// Parses a delivered message from raw bytes, allowing for arbitrary binary payloads
+    pub fn parse(buf: &[u8]) -> Result<DeliveredMessage, NatsParseError> {
+        DeliveredMessageRef::parse(buf).map(DeliveredMessage::from)
+    }
 }
 
 impl Display for DeliveredMessage {
@@ -375,27 +701,7 @@ impl FromStr for DeliveredMessage {
     type Err = NatsParseError;
 
     fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
-        let split = parser::split_header_and_payload(s);
-        match split {
-            None => Err(NatsParseError {
-                msg: "Failed to parse message - possibly not a 2-line message".to_string(),
-            }),
-            Some(split) => {
-                let res = parser::parse_msg_header(&split.0);
-                match res {
-                    Some(r) => Ok(DeliveredMessage {
-                        subject: r.subject,
-                        subscription_id: r.sid,
-                        reply_to: r.reply_to,
-                        payload_size: r.message_len,
-                        payload: split.1,
-                    }),
-                    None => Err(NatsParseError {
-                        msg: "Failed to parse delivered message".to_string(),
-                    }),
-                }
-            }
-        }
+        DeliveredMessage::parse(s.as_bytes())
     }
 }
 
@@ -406,41 +712,49 @@ impl FromStr for DeliveredMessage {
 /// ```
 #[derive(Debug, Clone, PartialEq)]
 pub struct SubscribeMessage {
-    pub subject: String,
+    pub subject: Subject,
     pub queue_group: Option<String>,
     pub subscription_id: usize,
 }
 
 impl SubscribeMessage {
-    /// Constructor to create a new subscription message
+    /// Constructor to create a new subscription message. Fails if the queue group is empty or
+    /// contains whitespace.
     pub fn new(
-        subject: String,
+        subject: Subject,
         queue_group: Option<String>,
         subscription_id: usize,
-    ) -> SubscribeMessage {
-        SubscribeMessage {
+    ) -> Result<SubscribeMessage, NatsParseError> {
+        if let Some(qg) = &queue_group {
+            subject::validate_queue_group(qg)?;
+        }
+        Ok(SubscribeMessage {
             subject,
             queue_group,
             subscription_id,
-        }
+        })
     }
 
     /// Efficient single-allocation conversion into a byte vector suitable for network transmission
     pub fn as_vec(
-        subject: &str,
+        subject: &Subject,
         queue_group: Option<&str>,
         subscription_id: usize,
     ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        let mut buffer = Vec::with_capacity(subject.len() + queue_group.map_or(0, |g| g.len() + 1));
-
-        write!(buffer, "SUB {}", subject)?;
-        if let Some(qg) = queue_group {
-            write!(buffer, " {}", qg)?;
-        }
-        write!(buffer, " {}\r\n", subscription_id)?;
-
+        let mut buffer = Vec::with_capacity(encode::sub_len(subject, queue_group, subscription_id));
+        encode::write_sub(&mut buffer, subject, queue_group, subscription_id)?;
         Ok(buffer)
     }
+
+    /// Parses a subscription message from raw bytes
+    pub fn parse(buf: &[u8]) -> Result<SubscribeMessage, NatsParseError> {
+        let r = parser::parse_sub_header(buf)?;
+        Ok(SubscribeMessage {
+            subscription_id: r.sid,
+            queue_group: r.queue_group,
+            subject: r.subject,
+        })
+    }
 }
 
 impl Display for SubscribeMessage {
@@ -456,17 +770,7 @@ impl FromStr for SubscribeMessage {
     type Err = NatsParseError;
 
     fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
-        let res = parser::parse_sub_header(s);
-        match res {
-            Some(r) => Ok(SubscribeMessage {
-                subscription_id: r.sid,
-                queue_group: r.queue_group,
-                subject: r.subject,
-            }),
-            None => Err(NatsParseError {
-                msg: "Failed to parse Subscribe message".to_string(),
-            }),
-        }
+        SubscribeMessage::parse(s.as_bytes())
     }
 }
 
@@ -495,14 +799,19 @@ impl UnsubscribeMessage {
         subscription_id: usize,
         max_messages: Option<usize>,
     ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        let mut buffer = Vec::with_capacity(16);
-        write!(buffer, "UNSUB {}", subscription_id)?;
-        if let Some(m) = max_messages {
-            write!(buffer, " {}", m)?;
-        }
-        write!(buffer, "\r\n")?;
+        let mut buffer = Vec::with_capacity(encode::unsub_len(subscription_id, max_messages));
+        encode::write_unsub(&mut buffer, subscription_id, max_messages)?;
         Ok(buffer)
     }
+
+    /// Parses an unsubscribe message from raw bytes
+    pub fn parse(buf: &[u8]) -> Result<UnsubscribeMessage, NatsParseError> {
+        let r = parser::parse_unsub_header(buf)?;
+        Ok(UnsubscribeMessage {
+            subscription_id: r.sid,
+            max_messages: r.max_messages,
+        })
+    }
 }
 
 impl Display for UnsubscribeMessage {
@@ -518,16 +827,7 @@ impl FromStr for UnsubscribeMessage {
     type Err = NatsParseError;
 
     fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
-        let res = parser::parse_unsub_header(s);
-        match res {
-            Some(r) => Ok(UnsubscribeMessage {
-                subscription_id: r.sid,
-                max_messages: r.max_messages,
-            }),
-            None => Err(NatsParseError {
-                msg: "Failed to parse Unsubscribe message".to_string(),
-            }),
-        }
+        UnsubscribeMessage::parse(s.as_bytes())
     }
 }
 
@@ -538,43 +838,54 @@ impl FromStr for UnsubscribeMessage {
 /// ```
 #[derive(Debug, Clone, PartialEq)]
 pub struct PublishMessage {
-    pub subject: String,
+    pub subject: Subject,
     pub reply_to: Option<String>,
     pub payload_size: usize,
-    pub payload: Vec<u8>,
+    pub payload: Payload,
 }
 
 impl PublishMessage {
-    /// Constructor to create a new publish message
-    pub fn new(subject: String, reply_to: Option<String>, payload: Vec<u8>) -> PublishMessage {
-        PublishMessage {
+    /// Constructor to create a new publish message. Fails if the subject contains wildcards or
+    /// the reply subject is not a valid subject without wildcards.
+    pub fn new(
+        subject: Subject,
+        reply_to: Option<String>,
+        payload: impl Into<Payload>,
+    ) -> Result<PublishMessage, NatsParseError> {
+        subject::validate_publish(&subject, reply_to.as_deref())?;
+        let payload = payload.into();
+        Ok(PublishMessage {
             subject,
             reply_to,
             payload_size: payload.len(),
             payload,
-        }
+        })
     }
 
-    /// Single-allocation conversion from source data to a byte vector suitable for transmission
+    /// Single-allocation conversion from source data to a byte vector suitable for transmission.
+    /// Fails if the subject contains wildcards.
     pub fn as_vec(
-        subject: &str,
+        subject: &Subject,
         reply_to: Option<&str>,
         payload: &[u8],
     ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        // don't need exact capacity, can get close
-        let capacity = 64 + payload.len();
-        let mut buffer = Vec::with_capacity(capacity);
-        write!(buffer, "PUB ")?;
-        write!(buffer, "{}", subject)?;
-        if let Some(rt) = reply_to {
-            write!(buffer, " {}", rt)?;
-        }
-        write!(buffer, " {}", payload.len())?; // does not allocate
-        write!(buffer, "\r\n")?;
-        buffer.extend_from_slice(payload);
-        write!(buffer, "\r\n")?;
+        let mut buffer = Vec::with_capacity(encode::pub_len(subject, reply_to, payload.len()));
+        encode::write_pub(&mut buffer, subject, reply_to, payload)?;
         Ok(buffer)
     }
+
+    /// Parses a publish message from raw bytes, allowing for arbitrary binary payloads
+    pub fn parse(buf: &[u8]) -> Result<PublishMessage, NatsParseError> {
+        let (control, start) = parser::control_line(buf, "PUB")?;
+        let hdr = parser::parse_pub_header(control)?;
+        let payload = parser::take_payload(buf, start, hdr.message_len, "PUB")?;
+        Ok(PublishMessage {
+            subject: hdr.subject,
+            reply_to: hdr.reply_to,
+            payload_size: hdr.message_len,
+            payload: to_payload(payload),
+        })
+    }
 }
 
 impl Display for PublishMessage {
@@ -603,64 +914,284 @@ impl FromStr for PublishMessage {
     type Err = NatsParseError;
 
     fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
-        let split = parser::split_header_and_payload(s);
-        match split {
-            None => Err(NatsParseError {
-                msg: "Failed to parse Publish message - possibly not a 2-line message".to_string(),
-            }),
-            Some(split) => {
-                let res = parser::parse_pub_header(&split.0);
-                match res {
-                    Some(r) => Ok(PublishMessage {
-                        subject: r.subject,
-                        reply_to: r.reply_to,
-                        payload_size: r.message_len,
-                        payload: split.1,
-                    }),
-                    None => Err(NatsParseError {
-                        msg: "Failed to parse Publish message".to_string(),
-                    }),
-                }
-            }
+        PublishMessage::parse(s.as_bytes())
+    }
+}
+
+/// Represents a message carrying headers, delivered by a NATS 2.2+ server. This message conforms
+/// to the following format from the NATS protocol documentation:
+/// ```text
+/// HMSG <subject> <sid> [reply-to] <#header bytes> <#total bytes>\r\n[headers]\r\n\r\n[payload]\r\n
+/// ```
+/// The header size covers the whole serialized `HeaderMap`, including its leading `NATS/1.0`
+/// version line and trailing empty line, and the total size covers both the headers and the
+/// payload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderDeliveredMessage {
+    pub subject: Subject,
+    pub subscription_id: usize,
+    pub reply_to: Option<String>,
+    pub header_size: usize,
+    pub total_size: usize,
+    pub headers: HeaderMap,
+    pub payload: Payload,
+}
+
+impl HeaderDeliveredMessage {
+    /// Constructor to build a new message from a given subject, header block, payload, etc
+    pub fn new(
+        subject: Subject,
+        subscription_id: usize,
+        reply_to: Option<String>,
+        headers: HeaderMap,
+        payload: impl Into<Payload>,
+    ) -> HeaderDeliveredMessage {
+        let payload = payload.into();
+        let header_size = headers.encoded_len();
+        HeaderDeliveredMessage {
+            subject,
+            subscription_id,
+            reply_to,
+            header_size,
+            total_size: header_size + payload.len(),
+            headers,
+            payload,
+        }
+    }
+
+    /// Single-allocation conversion from source data to a byte vector suitable for transmission
+    pub fn as_vec(
+        subject: &Subject,
+        subscription_id: usize,
+        reply_to: Option<&str>,
+        headers: &HeaderMap,
+        payload: &[u8],
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut buffer = Vec::with_capacity(encode::hmsg_len(
+            subject,
+            subscription_id,
+            reply_to,
+            headers,
+            payload.len(),
+        ));
+        encode::write_hmsg(
+            &mut buffer,
+            subject,
+            subscription_id,
+            reply_to,
+            headers,
+            payload,
+        )?;
+        Ok(buffer)
+    }
+
+    /// Parses a header message from raw bytes, allowing for arbitrary binary payloads
+    pub fn parse(buf: &[u8]) -> Result<HeaderDeliveredMessage, NatsParseError> {
+        HeaderDeliveredMessageRef::parse(buf).map(HeaderDeliveredMessage::from)
+    }
+}
+
+impl Display for HeaderDeliveredMessage {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), ::std::fmt::Error> {
+        // sizes are taken from the serialized header block so the output is always consistent
+        let headers = self.headers.to_string();
+        match self.reply_to {
+            None => write!(
+                f,
+                "HMSG {} {} {} {}\r\n{}{}\r\n",
+                self.subject,
+                self.subscription_id,
+                headers.len(),
+                headers.len() + self.payload.len(),
+                headers,
+                vec_to_str(&self.payload)
+            ),
+            Some(ref rt) => write!(
+                f,
+                "HMSG {} {} {} {} {}\r\n{}{}\r\n",
+                self.subject,
+                self.subscription_id,
+                rt,
+                headers.len(),
+                headers.len() + self.payload.len(),
+                headers,
+                vec_to_str(&self.payload)
+            ),
         }
     }
 }
 
-fn vec_to_str(bytes: &Vec<u8>) -> String {
-    let s = String::from_utf8(bytes.as_bytes().to_owned());
-    match s {
-        Ok(s) => s,
-        Err(_) => "<<BAD PAYLOAD>>".to_string(),
+impl FromStr for HeaderDeliveredMessage {
+    type Err = NatsParseError;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        HeaderDeliveredMessage::parse(s.as_bytes())
     }
 }
 
-/// Indicates an error occurred during parsing of a NATS protocol message. Do not use this
-/// type directly, instead use the error trait.
-#[derive(Debug)]
-pub struct NatsParseError {
-    msg: String,
+/// Represents a publish message carrying headers. This message conforms to the following format
+/// from the NATS protocol documentation:
+/// ```text
+/// HPUB <subject> [reply-to] <#header bytes> <#total bytes>\r\n[headers]\r\n\r\n[payload]\r\n
+/// ```
+/// The header size covers the whole serialized `HeaderMap`, including its leading `NATS/1.0`
+/// version line and trailing empty line, and the total size covers both the headers and the
+/// payload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderPublishMessage {
+    pub subject: Subject,
+    pub reply_to: Option<String>,
+    pub header_size: usize,
+    pub total_size: usize,
+    pub headers: HeaderMap,
+    pub payload: Payload,
 }
 
-impl Error for NatsParseError {
-    fn description(&self) -> &str {
-        &self.msg
+impl HeaderPublishMessage {
+    /// Constructor to create a new header publish message. Fails if the subject contains
+    /// wildcards or the reply subject is not a valid subject without wildcards.
+    pub fn new(
+        subject: Subject,
+        reply_to: Option<String>,
+        headers: HeaderMap,
+        payload: impl Into<Payload>,
+    ) -> Result<HeaderPublishMessage, NatsParseError> {
+        subject::validate_publish(&subject, reply_to.as_deref())?;
+        let payload = payload.into();
+        let header_size = headers.encoded_len();
+        Ok(HeaderPublishMessage {
+            subject,
+            reply_to,
+            header_size,
+            total_size: header_size + payload.len(),
+            headers,
+            payload,
+        })
     }
+
+    /// Single-allocation conversion from source data to a byte vector suitable for transmission.
+    /// Fails if the subject contains wildcards.
+    pub fn as_vec(
+        subject: &Subject,
+        reply_to: Option<&str>,
+        headers: &HeaderMap,
+        payload: &[u8],
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut buffer =
+            Vec::with_capacity(encode::hpub_len(subject, reply_to, headers, payload.len()));
+        encode::write_hpub(&mut buffer, subject, reply_to, headers, payload)?;
+        Ok(buffer)
+    }
+
+    /// Parses a header publish message from raw bytes, allowing for arbitrary binary payloads
+    pub fn parse(buf: &[u8]) -> Result<HeaderPublishMessage, NatsParseError> {
+        let (control, start) = parser::control_line(buf, "HPUB")?;
+        let hdr = parser::parse_hpub_header(control)?;
+        let (headers, payload) =
+            parser::split_headers_and_payload(buf, start, hdr.header_len, hdr.total_len, "HPUB")?;
+        let headers =
+            HeaderMap::from_bytes(headers).map_err(|e| e.with_verb("HPUB").offset_by(start))?;
+        Ok(HeaderPublishMessage {
+            subject: hdr.subject,
+            reply_to: hdr.reply_to,
+            header_size: hdr.header_len,
+            total_size: hdr.total_len,
+            headers,
+            payload: to_payload(payload),
+        })
+    }
+}
+
+impl Display for HeaderPublishMessage {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), ::std::fmt::Error> {
+        // sizes are taken from the serialized header block so the output is always consistent
+        let headers = self.headers.to_string();
+        match self.reply_to {
+            None => write!(
+                f,
+                "HPUB {} {} {}\r\n{}{}\r\n",
+                self.subject,
+                headers.len(),
+                headers.len() + self.payload.len(),
+                headers,
+                vec_to_str(&self.payload)
+            ),
+            Some(ref rt) => write!(
+                f,
+                "HPUB {} {} {} {}\r\n{}{}\r\n",
+                self.subject,
+                rt,
+                headers.len(),
+                headers.len() + self.payload.len(),
+                headers,
+                vec_to_str(&self.payload)
+            ),
+        }
+    }
+}
+
+impl FromStr for HeaderPublishMessage {
+    type Err = NatsParseError;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        HeaderPublishMessage::parse(s.as_bytes())
+    }
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
 }
 
-impl Display for NatsParseError {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "{}", self.msg)
+fn is_true(b: &bool) -> bool {
+    *b
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// Copies a payload out of the buffer it was parsed from
+fn to_payload(bytes: &[u8]) -> Payload {
+    Payload::from(bytes.to_vec())
+}
+
+// Display can only write text, so a payload that is not UTF-8 is replaced by a placeholder
+fn vec_to_str(bytes: &[u8]) -> String {
+    let s = String::from_utf8(bytes.as_bytes().to_owned());
+    match s {
+        Ok(s) => s,
+        Err(_) => "<<BAD PAYLOAD>>".to_string(),
     }
 }
 
+mod borrowed;
+#[cfg(feature = "tokio-codec")]
+mod codec;
+#[cfg(feature = "nkeys")]
+mod creds;
+mod decoder;
+mod encode;
+mod error;
+mod header;
+mod inbox;
+mod jetstream;
+mod kv;
+#[cfg(feature = "nkeys")]
+mod nkeys;
+mod nuid;
 mod parser;
+mod server_error;
+mod subject;
+mod sublist;
 
 #[cfg(test)]
 mod tests {
     use super::{
-        ConnectionInformation, DeliveredMessage, ProtocolMessage, PublishMessage,
+        ConnectionInformation, DeliveredMessage, Encode, HeaderDeliveredMessage, HeaderMap,
+        HeaderPublishMessage, ParseErrorKind, ProtocolMessage, PublishMessage, ServerError,
         ServerInformation, SubscribeMessage, UnsubscribeMessage,
     };
+    use std::error::Error;
     use std::str::FromStr;
 
     #[test]
@@ -702,7 +1233,7 @@ mod tests {
         assert_eq!(pubm.payload_size, 11);
         assert_eq!(pubm.subject, "FOO");
         assert_eq!(pubm.reply_to, None);
-        assert_eq!(pubm.payload, b"Hello NATS!");
+        assert_eq!(pubm.payload, &b"Hello NATS!"[..]);
         let out = format!("{}", pubm);
         assert_eq!(out, msg);
     }
@@ -714,7 +1245,7 @@ mod tests {
         assert_eq!(pubm.payload_size, 11);
         assert_eq!(pubm.subject, "FOO");
         assert_eq!(pubm.reply_to, None);
-        assert_eq!(pubm.payload, b"Hello NATS!");
+        assert_eq!(pubm.payload, &b"Hello NATS!"[..]);
         let out = format!("{}", pubm);
         assert_eq!(out, "PUB FOO 11\r\nHello NATS!\r\n");
     }
@@ -727,7 +1258,7 @@ mod tests {
         assert_eq!(mmsg.payload_size, 11);
         assert_eq!(mmsg.subscription_id, 9);
         assert_eq!(mmsg.subject, "FOO.BAR");
-        assert_eq!(mmsg.payload, b"Hello World");
+        assert_eq!(mmsg.payload, &b"Hello World"[..]);
         let out = format!("{}", mmsg);
         assert_eq!(out, msg);
     }
@@ -740,12 +1271,13 @@ mod tests {
         assert_eq!(mmsg.payload_size, 11);
         assert_eq!(mmsg.subscription_id, 9);
         assert_eq!(mmsg.subject, "FOO.BAR");
-        assert_eq!(mmsg.payload, b"Hello World");
+        assert_eq!(mmsg.payload, &b"Hello World"[..]);
         let out = format!("{}", mmsg);
         assert_eq!(out, "MSG FOO.BAR 9 INBOX.34 11\r\nHello World\r\n");
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn serverinfo_roundtrip() {
         let msg = r#"INFO {"server_id":"1ec445b504f4edfb4cf7927c707dd717",
         "version":"0.6.6","go":"go1.4.2","host":"0.0.0.0",
@@ -757,16 +1289,43 @@ mod tests {
         if let Ok(info) = si {
             assert_eq!(info.connect_urls, None);
             assert_eq!(info.server_id, "1ec445b504f4edfb4cf7927c707dd717");
-            assert_eq!(info.go, "go1.4.2");
+            assert_eq!(info.go.as_deref(), Some("go1.4.2"));
             assert_eq!(info.version, "0.6.6");
             assert_eq!(info.max_payload, 1048576);
             assert_eq!(info.tls_required, false);
             assert_eq!(info.port, 4222);
-            assert_eq!(info.host, "0.0.0.0");
+            assert_eq!(info.host.as_deref(), Some("0.0.0.0"));
         }
     }
 
     #[test]
+    fn serverinfo_modern_fields() {
+        let msg = r#"INFO {"server_id":"NCXJ3HSU","server_name":"n1","version":"2.10.7","proto":1,"git_commit":"fa8464d","go":"go1.21.5","host":"0.0.0.0","port":4222,"headers":true,"tls_available":true,"max_payload":1048576,"jetstream":true,"client_id":5,"client_ip":"127.0.0.1","cluster":"c1","domain":"hub","connect_urls":["10.0.0.1:4222"],"ldm":true,"xkey":"XAB3","future_field":{"a":[1,2]}}"#;
+        let info = ServerInformation::from_str(msg).unwrap();
+        assert!(info.headers);
+        assert!(info.jetstream);
+        assert!(info.ldm);
+        assert!(info.tls_available);
+        assert!(!info.auth_required);
+        assert_eq!(info.domain.as_deref(), Some("hub"));
+        assert_eq!(info.client_ip.as_deref(), Some("127.0.0.1"));
+        assert_eq!(info.xkey.as_deref(), Some("XAB3"));
+        assert_eq!(info.extra["future_field"]["a"][1], 2);
+
+        let out = format!("{}", info);
+        assert!(out.contains(r#""future_field":{"a":[1,2]}"#));
+        assert_eq!(ServerInformation::from_str(&out).unwrap(), info);
+
+        let info =
+            ServerInformation::from_str(r#"INFO {"server_id":"x","version":"2.10.0","port":4222}"#)
+                .unwrap();
+        assert_eq!(info.go, None);
+        assert_eq!(info.host, None);
+        assert!(info.extra.is_empty());
+    }
+
+    #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn connect_roundtrip() {
         let msg = r#"CONNECT {"verbose":false,"pedantic":false,"tls_required":false,"lang":"go","name":"testing","version":"1.2.2","protocol":1}"#;
         let ci = ConnectionInformation::from_str(msg);
@@ -782,13 +1341,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn connect_modern_fields() {
+        let msg = r#"CONNECT {"verbose":false,"pedantic":false,"tls_required":false,"lang":"rust","name":"","version":"0.1.0","protocol":1,"echo":false,"headers":true,"no_responders":true,"nkey":"UABC","sig":"c2ln","future":1}"#;
+        let info = ConnectionInformation::from_str(msg).unwrap();
+        assert!(!info.echo);
+        assert!(info.headers);
+        assert!(info.no_responders);
+        assert_eq!(info.nkey.as_deref(), Some("UABC"));
+        assert_eq!(info.extra["future"], 1);
+        assert_eq!(format!("{}", info), format!("{}\r\n", msg));
+
+        let built = ConnectionInformation::builder()
+            .version("0.1.0")
+            .echo(false)
+            .no_responders(true)
+            .nkey("UABC", "c2ln")
+            .extra("future", 1.into())
+            .build();
+        assert_eq!(built, info);
+        assert!(ConnectionInformation::builder().build().echo);
+    }
+
     #[test]
     fn enum_round() {
         let publish = ProtocolMessage::Publish(PublishMessage {
-            subject: "workdispatch".to_string(),
+            subject: "workdispatch".parse().unwrap(),
             reply_to: None,
             payload_size: 11,
-            payload: b"Hello World".to_vec(),
+            payload: b"Hello World"[..].into(),
         });
         let out = format!("{}", publish);
         let pub2 = ProtocolMessage::from_str(&out).unwrap();
@@ -798,7 +1379,8 @@ mod tests {
 
     #[test]
     fn pubmessage_bytes_roundtrip() {
-        let vec = PublishMessage::as_vec("workdispatch", None, b"Hello World").unwrap();
+        let vec =
+            PublishMessage::as_vec(&"workdispatch".parse().unwrap(), None, b"Hello World").unwrap();
         let outstring = String::from_utf8(vec).unwrap();
         assert_eq!(
             outstring,
@@ -807,11 +1389,13 @@ mod tests {
                 b"Hello World".len()
             )
         );
+        assert!(PublishMessage::as_vec(&"work.*".parse().unwrap(), None, b"").is_err());
     }
 
     #[test]
     fn submessage_bytes_roundtrip() {
-        let vec = SubscribeMessage::as_vec("workdispatch", Some("myservice"), 99).unwrap();
+        let vec = SubscribeMessage::as_vec(&"workdispatch".parse().unwrap(), Some("myservice"), 99)
+            .unwrap();
         let outstring = String::from_utf8(vec).unwrap();
         assert_eq!(outstring, format!("SUB workdispatch myservice 99\r\n"));
     }
@@ -822,4 +1406,247 @@ mod tests {
         let outstring = String::from_utf8(vec).unwrap();
         assert_eq!(outstring, format!("UNSUB 84 1\r\n"));
     }
+
+    #[test]
+    fn hpub_roundtrip() {
+        let msg = "HPUB FOO INBOX.7 22 33\r\nNATS/1.0\r\nBar: Baz\r\n\r\nHello NATS!\r\n";
+        let hpub = HeaderPublishMessage::from_str(msg).unwrap();
+        assert_eq!(hpub.subject, "FOO");
+        assert_eq!(hpub.reply_to, Some("INBOX.7".to_string()));
+        assert_eq!(hpub.header_size, 22);
+        assert_eq!(hpub.total_size, 33);
+        assert_eq!(hpub.headers.get("Bar"), Some("Baz"));
+        assert_eq!(hpub.payload, &b"Hello NATS!"[..]);
+        let out = format!("{}", hpub);
+        assert_eq!(out, msg);
+    }
+
+    #[test]
+    fn hpub_size_mismatch() {
+        let msg = "HPUB FOO 22 40\r\nNATS/1.0\r\nBar: Baz\r\n\r\nHello NATS!\r\n";
+        assert!(HeaderPublishMessage::from_str(msg).is_err());
+    }
+
+    #[test]
+    fn hmsg_roundtrip() {
+        let msg = "HMSG FOO.BAR 9 22 22\r\nNATS/1.0\r\nBar: Baz\r\n\r\n\r\n";
+        let hmsg = HeaderDeliveredMessage::from_str(msg).unwrap();
+        assert_eq!(hmsg.subject, "FOO.BAR");
+        assert_eq!(hmsg.subscription_id, 9);
+        assert_eq!(hmsg.reply_to, None);
+        assert_eq!(hmsg.headers.get("Bar"), Some("Baz"));
+        assert!(hmsg.payload.is_empty());
+        let out = format!("{}", hmsg);
+        assert_eq!(out, msg);
+    }
+
+    #[test]
+    fn enum_header_dispatch() {
+        let hpub =
+            ProtocolMessage::from_str("HPUB FOO 12 17\r\nNATS/1.0\r\n\r\nHello\r\n").unwrap();
+        match hpub {
+            ProtocolMessage::HeaderPublish(m) => assert_eq!(m.payload, &b"Hello"[..]),
+            _ => panic!("expected HPUB"),
+        }
+
+        let hmsg = HeaderDeliveredMessage::new(
+            "FOO".parse().unwrap(),
+            4,
+            Some("INBOX.1".to_string()),
+            HeaderMap::new(),
+            b"Hello".to_vec(),
+        );
+        let msg = ProtocolMessage::HeaderMessage(hmsg);
+        let out = format!("{}", msg);
+        assert_eq!(out, "HMSG FOO 4 INBOX.1 12 17\r\nNATS/1.0\r\n\r\nHello\r\n");
+        assert_eq!(ProtocolMessage::from_str(&out).unwrap(), msg);
+    }
+
+    #[test]
+    fn hpubmessage_bytes_roundtrip() {
+        let vec = HeaderPublishMessage::as_vec(
+            &"workdispatch".parse().unwrap(),
+            Some("INBOX.1"),
+            &HeaderMap::new(),
+            b"Hello World",
+        )
+        .unwrap();
+        let outstring = String::from_utf8(vec).unwrap();
+        assert_eq!(
+            outstring,
+            "HPUB workdispatch INBOX.1 12 23\r\nNATS/1.0\r\n\r\nHello World\r\n"
+        );
+    }
+
+    #[test]
+    fn hmsg_no_responders() {
+        let msg = "HMSG INBOX.1 3 16 16\r\nNATS/1.0 503\r\n\r\n\r\n";
+        let hmsg = HeaderDeliveredMessage::from_str(msg).unwrap();
+        assert!(hmsg.headers.is_no_responders());
+        assert!(hmsg.headers.is_empty());
+        assert!(hmsg.payload.is_empty());
+        assert_eq!(format!("{}", hmsg), msg);
+    }
+
+    #[test]
+    fn hpub_normalizes_header_sizes() {
+        let msg = "HPUB FOO 21 23\r\nNATS/1.0\r\nBar:Baz\r\n\r\nHi\r\n";
+        let hpub = HeaderPublishMessage::from_str(msg).unwrap();
+        assert_eq!(hpub.header_size, 21);
+        assert_eq!(
+            format!("{}", hpub),
+            "HPUB FOO 22 24\r\nNATS/1.0\r\nBar: Baz\r\n\r\nHi\r\n"
+        );
+    }
+
+    #[test]
+    fn pub_payload_with_crlf() {
+        let msg = "PUB FOO 11\r\nHello\r\nNATS\r\n";
+        let pubm = PublishMessage::from_str(msg).unwrap();
+        assert_eq!(pubm.payload, &b"Hello\r\nNATS"[..]);
+        assert_eq!(format!("{}", pubm), msg);
+    }
+
+    #[test]
+    fn pub_payload_size_mismatch() {
+        assert!(PublishMessage::from_str("PUB FOO 5\r\nHello NATS!\r\n").is_err());
+        assert!(PublishMessage::from_str("PUB FOO 20\r\nHello NATS!\r\n").is_err());
+        assert!(PublishMessage::from_str("PUB FOO 11\r\nHello NATS!").is_err());
+    }
+
+    #[test]
+    fn msg_multiline_payload_roundtrip() {
+        let payload = "caf\u{e9}\r\n\r\n\u{1f980}".as_bytes().to_vec();
+        let vec = PublishMessage::as_vec(&"proto.in".parse().unwrap(), None, &payload).unwrap();
+        let wire = String::from_utf8(vec).unwrap();
+        let pubm = PublishMessage::from_str(&wire).unwrap();
+        assert_eq!(pubm.payload, payload);
+
+        let mmsg = DeliveredMessage::new("proto.out".parse().unwrap(), 1, None, payload.clone());
+        let wire = format!("{}", mmsg);
+        assert_eq!(DeliveredMessage::from_str(&wire).unwrap(), mmsg);
+        assert!(DeliveredMessage::from_str("MSG FOO 1 3\r\nHello\r\n").is_err());
+    }
+
+    #[test]
+    fn parse_binary_payloads() {
+        let payload = b"\x08\x96\x01\r\n\xff\x00".to_vec();
+        let wire = PublishMessage::as_vec(&"proto.in".parse().unwrap(), Some("INBOX.1"), &payload)
+            .unwrap();
+        match ProtocolMessage::parse(&wire).unwrap() {
+            ProtocolMessage::Publish(m) => {
+                assert_eq!(m.payload, payload);
+                assert_eq!(m.reply_to, Some("INBOX.1".to_string()));
+            }
+            _ => panic!("expected PUB"),
+        }
+
+        let mut wire = b"MSG proto.out 7 7\r\n".to_vec();
+        wire.extend_from_slice(&payload);
+        wire.extend_from_slice(b"\r\n");
+        let mmsg = DeliveredMessage::parse(&wire).unwrap();
+        assert_eq!(mmsg.subscription_id, 7);
+        assert_eq!(mmsg.payload, payload);
+        assert!(DeliveredMessage::from_str(&String::from_utf8_lossy(&wire)).is_err());
+
+        let mut wire = b"HMSG proto.out 7 12 19\r\nNATS/1.0\r\n\r\n".to_vec();
+        wire.extend_from_slice(&payload);
+        wire.extend_from_slice(b"\r\n");
+        let hmsg = HeaderDeliveredMessage::parse(&wire).unwrap();
+        assert_eq!(hmsg.payload, payload);
+    }
+
+    #[test]
+    fn parse_bytes_control_messages() {
+        assert_eq!(
+            ProtocolMessage::parse(b"PING\r\n").unwrap(),
+            ProtocolMessage::Ping
+        );
+        assert_eq!(
+            ProtocolMessage::parse(b"-ERR 'Unknown Protocol Operation'\r\n").unwrap(),
+            ProtocolMessage::Error(ServerError::from("Unknown Protocol Operation"))
+        );
+        let sub = SubscribeMessage::parse(b"SUB FOO q 3\r\n").unwrap();
+        assert_eq!(sub.queue_group, Some("q".to_string()));
+        let unsub = UnsubscribeMessage::parse(b"UNSUB 3\r\n").unwrap();
+        assert_eq!(unsub.max_messages, None);
+        let info = ServerInformation::parse(
+            br#"INFO {"server_id":"x","version":"2.0.0","go":"go1.12","host":"0.0.0.0","port":4222}"#,
+        )
+        .unwrap();
+        assert_eq!(info.port, 4222);
+        assert!(ProtocolMessage::parse(b"BOGUS\r\n").is_err());
+        assert!(SubscribeMessage::parse(b"SUB \xff\xfe 3\r\n").is_err());
+    }
+
+    #[test]
+    fn err_roundtrip() {
+        let err = ProtocolMessage::Error(ServerError::from("Authorization Violation"));
+        assert_eq!(format!("{}", err), "-ERR 'Authorization Violation'");
+        let out = String::from_utf8(err.encode().unwrap()).unwrap();
+        assert_eq!(out, "-ERR 'Authorization Violation'\r\n");
+        assert_eq!(ProtocolMessage::from_str(&out).unwrap(), err);
+
+        let out = "-ERR 'Permissions Violation for Publish to \"secret.>\"'\r\n";
+        match ProtocolMessage::from_str(out).unwrap() {
+            ProtocolMessage::Error(e) => {
+                assert_eq!(e.subject(), Some("secret.>"));
+                assert!(!e.is_fatal());
+                let encoded = ProtocolMessage::Error(e).encode().unwrap();
+                assert_eq!(encoded, out.as_bytes());
+            }
+            _ => panic!("expected -ERR"),
+        }
+    }
+
+    #[test]
+    fn msgmessage_bytes_roundtrip() {
+        let vec = DeliveredMessage::as_vec(
+            &"workdispatch".parse().unwrap(),
+            3,
+            Some("INBOX.1"),
+            b"\x00\xff",
+        )
+        .unwrap();
+        assert_eq!(vec, b"MSG workdispatch 3 INBOX.1 2\r\n\x00\xff\r\n");
+        let mmsg = DeliveredMessage::parse(&vec).unwrap();
+        assert_eq!(mmsg.payload, &b"\x00\xff"[..]);
+
+        let vec = HeaderDeliveredMessage::as_vec(
+            &"workdispatch".parse().unwrap(),
+            3,
+            None,
+            &HeaderMap::new(),
+            b"hi",
+        )
+        .unwrap();
+        assert_eq!(vec, b"HMSG workdispatch 3 12 14\r\nNATS/1.0\r\n\r\nhi\r\n");
+    }
+
+    #[test]
+    fn parse_error_kinds() {
+        let err = ProtocolMessage::parse(b"BOGUS FOO\r\n").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::UnknownOperation);
+        assert_eq!(err.verb(), Some("BOGUS"));
+        assert_eq!(err.offset(), 0);
+
+        let err = ProtocolMessage::parse(b"INFO {\"port\": }\r\n").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::InvalidJson);
+        assert_eq!(err.verb(), Some("INFO"));
+        assert!(err.source().is_some());
+
+        let err = ProtocolMessage::parse(b"PUB FOO 5\r\nHello NATS!\r\n").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::PayloadSizeMismatch);
+        assert_eq!(err.offset(), 16);
+
+        let err = ProtocolMessage::parse(b"MSG FOO 1 11\r\nHello").unwrap_err();
+        assert!(err.is_incomplete());
+
+        let err = ProtocolMessage::parse(b"SUB FOO\r\n").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::MalformedControlLine);
+        assert_eq!(err.verb(), Some("SUB"));
+
+        let err = ProtocolMessage::parse(b"HPUB FOO 12 14\r\nNATS/2.0\r\n\r\nhi\r\n").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::MalformedHeaders);
+    }
 }
@@ -0,0 +1,292 @@
+use crate::{ConnectionInformation, ConnectionInformationBuilder};
+use data_encoding::{BASE32_NOPAD, BASE64URL_NOPAD};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+const PREFIX_BYTE_SEED: u8 = 18 << 3;
+
+/// The role of an nkey, given by the first character of its public key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyKind {
+    User,
+    Account,
+    Operator,
+    Server,
+    Cluster,
+}
+
+impl KeyKind {
+    fn prefix_byte(self) -> u8 {
+        match self {
+            KeyKind::User => 20 << 3,
+            KeyKind::Account => 0,
+            KeyKind::Operator => 14 << 3,
+            KeyKind::Server => 13 << 3,
+            KeyKind::Cluster => 2 << 3,
+        }
+    }
+
+    fn from_prefix_byte(b: u8) -> Option<KeyKind> {
+        [
+            KeyKind::User,
+            KeyKind::Account,
+            KeyKind::Operator,
+            KeyKind::Server,
+            KeyKind::Cluster,
+        ]
+        .iter()
+        .cloned()
+        .find(|k| k.prefix_byte() == b)
+    }
+}
+
+/// Indicates an nkey could not be decoded or a signature did not verify
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NkeyError {
+    /// The key is not valid base32
+    InvalidEncoding,
+    /// The key does not have the length of an encoded Ed25519 key
+    InvalidLength,
+    /// The checksum at the end of the key does not match its contents
+    InvalidChecksum,
+    /// The key does not start with a known key kind, preceded by `S` for a seed
+    InvalidPrefix,
+    /// The signature was not produced by the key
+    InvalidSignature,
+}
+
+impl Error for NkeyError {}
+
+impl Display for NkeyError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            NkeyError::InvalidEncoding => write!(f, "Invalid nkey - not valid base32"),
+            NkeyError::InvalidLength => write!(f, "Invalid nkey - wrong length"),
+            NkeyError::InvalidChecksum => write!(f, "Invalid nkey - checksum mismatch"),
+            NkeyError::InvalidPrefix => write!(f, "Invalid nkey - unknown prefix"),
+            NkeyError::InvalidSignature => write!(f, "Signature verification failed"),
+        }
+    }
+}
+
+/// An Ed25519 key pair decoded from an nkey seed such as `SUA...` for a user or `SAA...` for an
+/// account, used to sign the nonce of a server's `INFO`. Available with the `nkeys` feature.
+///
+/// ```rust
+/// extern crate nats_types;
+///
+/// use nats_types::{ConnectionInformation, KeyPair};
+///
+/// let key = KeyPair::from_seed("SUAACAQDAQCQMBYIBEFAWDANBYHRAEISCMKBKFQXDAMRUGY4DUPB6IC5CQ").unwrap();
+/// let connect = ConnectionInformation::builder()
+///     .sign_nonce(&key, "PXoWU7zWAMt75FY")
+///     .build();
+/// assert_eq!(connect.nkey, Some(key.public_key()));
+/// assert!(connect.sig.is_some());
+/// ```
+pub struct KeyPair {
+    kind: KeyKind,
+    signing_key: SigningKey,
+}
+
+impl KeyPair {
+    /// Decodes a key pair from its encoded seed
+    pub fn from_seed(seed: &str) -> Result<KeyPair, NkeyError> {
+        let raw = decode(seed, 36)?;
+        if raw[0] & 0xf8 != PREFIX_BYTE_SEED {
+            return Err(NkeyError::InvalidPrefix);
+        }
+        let kind = KeyKind::from_prefix_byte(((raw[0] & 0x07) << 5) | ((raw[1] & 0xf8) >> 3))
+            .ok_or(NkeyError::InvalidPrefix)?;
+        let mut seed = [0u8; 32];
+        seed.copy_from_slice(&raw[2..34]);
+        Ok(KeyPair {
+            kind,
+            signing_key: SigningKey::from_bytes(&seed),
+        })
+    }
+
+    /// The role of the key
+    pub fn kind(&self) -> KeyKind {
+        self.kind
+    }
+
+    /// The encoded public key, e.g. `UB43...` for a user
+    pub fn public_key(&self) -> String {
+        let mut raw = Vec::with_capacity(35);
+        raw.push(self.kind.prefix_byte());
+        raw.extend_from_slice(self.signing_key.verifying_key().as_bytes());
+        raw.extend_from_slice(&crc16(&raw).to_le_bytes());
+        BASE32_NOPAD.encode(&raw)
+    }
+
+    /// Produces the Ed25519 signature of the given data
+    pub fn sign(&self, data: &[u8]) -> Vec<u8> {
+        self.signing_key.sign(data).to_bytes().to_vec()
+    }
+
+    /// Signs the nonce of a server's `INFO`, producing the unpadded base64url signature
+    /// expected in the `sig` of a `CONNECT`
+    pub fn sign_nonce(&self, nonce: &str) -> String {
+        BASE64URL_NOPAD.encode(&self.sign(nonce.as_bytes()))
+    }
+}
+
+impl fmt::Debug for KeyPair {
+    // the seed is deliberately left out
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("KeyPair")
+            .field("kind", &self.kind)
+            .field("public_key", &self.public_key())
+            .finish()
+    }
+}
+
+impl ConnectionInformation {
+    /// Authenticates with an nkey by setting the public key and the signature of the server's
+    /// nonce. Available with the `nkeys` feature.
+    pub fn sign_nonce(&mut self, key: &KeyPair, nonce: &str) {
+        self.nkey = Some(key.public_key());
+        self.sig = Some(key.sign_nonce(nonce));
+    }
+}
+
+impl ConnectionInformationBuilder {
+    /// Authenticates with an nkey by signing the server's nonce. Available with the `nkeys`
+    /// feature.
+    pub fn sign_nonce(self, key: &KeyPair, nonce: &str) -> Self {
+        self.nkey(key.public_key(), key.sign_nonce(nonce))
+    }
+}
+
+/// Verifies an Ed25519 signature of the given data against an encoded public key such as
+/// `UB43...`, returning the kind of the key. Available with the `nkeys` feature.
+pub fn verify_signature(
+    public_key: &str,
+    data: &[u8],
+    signature: &[u8],
+) -> Result<KeyKind, NkeyError> {
+    let raw = decode(public_key, 35)?;
+    let kind = KeyKind::from_prefix_byte(raw[0]).ok_or(NkeyError::InvalidPrefix)?;
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&raw[1..33]);
+    let key = VerifyingKey::from_bytes(&key).map_err(|_| NkeyError::InvalidEncoding)?;
+    let signature = Signature::from_slice(signature).map_err(|_| NkeyError::InvalidSignature)?;
+    key.verify(data, &signature)
+        .map_err(|_| NkeyError::InvalidSignature)?;
+    Ok(kind)
+}
+
+// Decodes an encoded key of the given length, checking and removing its trailing checksum
+fn decode(key: &str, len: usize) -> Result<Vec<u8>, NkeyError> {
+    let mut raw = BASE32_NOPAD
+        .decode(key.trim().as_bytes())
+        .map_err(|_| NkeyError::InvalidEncoding)?;
+    if raw.len() != len {
+        return Err(NkeyError::InvalidLength);
+    }
+    let checksum = raw.split_off(len - 2);
+    if crc16(&raw).to_le_bytes() != checksum[..] {
+        return Err(NkeyError::InvalidChecksum);
+    }
+    Ok(raw)
+}
+
+// CRC-16/XMODEM, as used by the nkey encoding
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |crc, b| {
+        (0..8).fold(crc ^ (u16::from(*b) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{verify_signature, KeyKind, KeyPair, NkeyError};
+    use crate::ConnectionInformation;
+
+    // generated independently from fixed seed bytes with OpenSSL's Ed25519
+    const USER_SEED: &str = "SUAACAQDAQCQMBYIBEFAWDANBYHRAEISCMKBKFQXDAMRUGY4DUPB6IC5CQ";
+    const USER_PUBLIC: &str = "UB43KVROR7TFJ6KAPCYRF2FJROTZAH4FHLTJLPWX4DRZCC5NASLGJBFE";
+    const USER_SIG: &str =
+        "RkyUfhj0hZV4SGosA4w8Z2jQjAdZCALBJzhNdhfW6581WHG7tdqsG6pGlr5MATr_YsnnyJOjpr9o2S3IT6iUBA";
+    const ACCOUNT_SEED: &str = "SAAGIZLGM5UGS2TLNRWW433QOFZHG5DVOZ3XQ6L2PN6H27T7QCAYFA5W7M";
+    const ACCOUNT_PUBLIC: &str = "AAF3YNDKK5THYOABEC6ZY76X4UOSYX675I342L236QC3FRV7N4WXRFLZ";
+    const ACCOUNT_SIG: &str =
+        "8rS2PvbKU_m9lzGlLb86IeVvEIcc9hIH8EO8gzNvAXUJkrcLXyunxWEhbEJC4QUfwJpsix98oU2vIfOV8HBHDw";
+    const NONCE: &str = "PXoWU7zWAMt75FY";
+
+    #[test]
+    fn known_vectors() {
+        let user = KeyPair::from_seed(USER_SEED).unwrap();
+        assert_eq!(user.kind(), KeyKind::User);
+        assert_eq!(user.public_key(), USER_PUBLIC);
+        assert_eq!(user.sign_nonce(NONCE), USER_SIG);
+
+        let account = KeyPair::from_seed(ACCOUNT_SEED).unwrap();
+        assert_eq!(account.kind(), KeyKind::Account);
+        assert_eq!(account.public_key(), ACCOUNT_PUBLIC);
+        assert_eq!(account.sign_nonce(NONCE), ACCOUNT_SIG);
+        assert!(!format!("{:?}", account).contains(ACCOUNT_SEED));
+    }
+
+    #[test]
+    fn verify_signatures() {
+        let key = KeyPair::from_seed(USER_SEED).unwrap();
+        let sig = key.sign(NONCE.as_bytes());
+        assert_eq!(
+            verify_signature(USER_PUBLIC, NONCE.as_bytes(), &sig),
+            Ok(KeyKind::User)
+        );
+        assert_eq!(
+            verify_signature(ACCOUNT_PUBLIC, NONCE.as_bytes(), &sig),
+            Err(NkeyError::InvalidSignature)
+        );
+        assert_eq!(
+            verify_signature(USER_PUBLIC, b"other", &sig),
+            Err(NkeyError::InvalidSignature)
+        );
+        assert!(verify_signature(USER_SEED, NONCE.as_bytes(), &sig).is_err());
+    }
+
+    #[test]
+    fn invalid_seeds() {
+        assert_eq!(
+            KeyPair::from_seed("not a seed").unwrap_err(),
+            NkeyError::InvalidEncoding
+        );
+        assert_eq!(
+            KeyPair::from_seed(&USER_SEED[..48]).unwrap_err(),
+            NkeyError::InvalidLength
+        );
+        let corrupted = USER_SEED.replace("MBYIB", "MBYIC");
+        assert_eq!(
+            KeyPair::from_seed(&corrupted).unwrap_err(),
+            NkeyError::InvalidChecksum
+        );
+        // a public key is not a seed
+        let padded = format!("{}AAAA", USER_PUBLIC);
+        assert!(KeyPair::from_seed(&padded).is_err());
+    }
+
+    #[test]
+    fn connect_signature() {
+        let key = KeyPair::from_seed(USER_SEED).unwrap();
+        let mut connect = ConnectionInformation::builder().headers(true).build();
+        connect.sign_nonce(&key, NONCE);
+        assert_eq!(connect.nkey.as_deref(), Some(USER_PUBLIC));
+        assert_eq!(connect.sig.as_deref(), Some(USER_SIG));
+
+        let out = connect.to_string();
+        assert!(out.contains(&format!(r#""nkey":"{}","sig":"{}""#, USER_PUBLIC, USER_SIG)));
+    }
+}
@@ -0,0 +1,120 @@
+use rand::rngs::OsRng;
+use rand::{thread_rng, Rng};
+use std::cell::RefCell;
+
+pub(crate) const DIGITS: &[u8; 62] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const BASE: u64 = 62;
+const PREFIX_LEN: usize = 12;
+const SEQ_LEN: usize = 10;
+const MAX_SEQ: u64 = 839_299_365_868_340_224; // 62^10
+const MIN_INC: u64 = 33;
+const MAX_INC: u64 = 333;
+
+/// The length of a NUID
+pub const NUID_LEN: usize = PREFIX_LEN + SEQ_LEN;
+
+/// A generator of NUIDs, the unique identifiers used by NATS clients for inboxes. A NUID is 22
+/// base62 characters: a 12 character random prefix followed by a 10 character sequence that
+/// advances by a random increment. When the sequence is exhausted, the prefix is re-randomized
+/// and the sequence starts over at a random point.
+///
+/// `next_nuid` draws from a generator local to the calling thread.
+#[derive(Debug, Clone)]
+pub struct Nuid {
+    prefix: [u8; PREFIX_LEN],
+    seq: u64,
+    inc: u64,
+}
+
+impl Default for Nuid {
+    fn default() -> Self {
+        Nuid::new()
+    }
+}
+
+impl Nuid {
+    /// Creates a generator with a random prefix and sequence
+    pub fn new() -> Nuid {
+        let mut nuid = Nuid {
+            prefix: [0; PREFIX_LEN],
+            seq: 0,
+            inc: 0,
+        };
+        nuid.randomize_prefix();
+        nuid.reset_sequential();
+        nuid
+    }
+
+    /// Replaces the prefix with one drawn from the operating system's secure random source
+    pub fn randomize_prefix(&mut self) {
+        let mut rng = OsRng;
+        for c in self.prefix.iter_mut() {
+            *c = DIGITS[rng.gen_range(0..DIGITS.len())];
+        }
+    }
+
+    fn reset_sequential(&mut self) {
+        let mut rng = thread_rng();
+        self.seq = rng.gen_range(0..MAX_SEQ);
+        self.inc = rng.gen_range(MIN_INC..MAX_INC);
+    }
+
+    /// Generates the next NUID
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> String {
+        self.seq += self.inc;
+        if self.seq >= MAX_SEQ {
+            self.randomize_prefix();
+            self.reset_sequential();
+        }
+        let mut out = [0u8; NUID_LEN];
+        out[..PREFIX_LEN].copy_from_slice(&self.prefix);
+        let mut seq = self.seq;
+        for c in out[PREFIX_LEN..].iter_mut().rev() {
+            *c = DIGITS[(seq % BASE) as usize];
+            seq /= BASE;
+        }
+        // every byte was taken from DIGITS
+        out.iter().map(|c| *c as char).collect()
+    }
+}
+
+thread_local! {
+    static NUID: RefCell<Nuid> = RefCell::new(Nuid::new());
+}
+
+/// Generates a NUID from a generator local to the calling thread
+pub fn next_nuid() -> String {
+    NUID.with(|n| n.borrow_mut().next())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{next_nuid, Nuid, DIGITS, MAX_SEQ, NUID_LEN, PREFIX_LEN};
+    use std::collections::HashSet;
+
+    #[test]
+    fn nuid_format() {
+        let nuid = next_nuid();
+        assert_eq!(nuid.len(), NUID_LEN);
+        assert!(nuid.bytes().all(|c| DIGITS.contains(&c)));
+    }
+
+    #[test]
+    fn nuid_unique() {
+        let mut gen = Nuid::new();
+        let seen: HashSet<String> = (0..10_000).map(|_| gen.next()).collect();
+        assert_eq!(seen.len(), 10_000);
+    }
+
+    #[test]
+    fn nuid_rollover() {
+        let mut gen = Nuid::new();
+        let first = gen.next();
+        gen.seq = MAX_SEQ - 1;
+        let rolled = gen.next();
+        assert_ne!(first[..PREFIX_LEN], rolled[..PREFIX_LEN]);
+        assert!(gen.seq < MAX_SEQ);
+    }
+}
@@ -3,30 +3,80 @@
 // and parser combinators. I would not have been able to write any of the code in this file
 // without their assistance.
 
-use nom::types::CompleteStr;
-
+use crate::error::{NatsParseError, ParseErrorKind};
+use crate::subject::validate_literal;
+use crate::Subject;
+use nom::types::CompleteByteSlice;
+use nom::{Context, IResult};
+use std::str;
 
 // MSG <subject> <sid> [reply-to] <#bytes>\r\n[payload]\r\n
 #[derive(Debug)]
 pub struct MessageHeader {
-    pub subject: String,
+    pub subject: Subject,
     pub sid: usize,
     pub reply_to: Option<String>,
     pub message_len: usize,
 }
 
+// MSG control line borrowing from the buffer it was parsed from
+#[derive(Debug)]
+pub struct MessageHeaderRef<'a> {
+    pub subject: &'a str,
+    pub sid: usize,
+    pub reply_to: Option<&'a str>,
+    pub message_len: usize,
+}
+
+impl<'a> From<MessageHeaderRef<'a>> for MessageHeader {
+    fn from(hdr: MessageHeaderRef<'a>) -> Self {
+        MessageHeader {
+            subject: Subject::from_validated(hdr.subject),
+            sid: hdr.sid,
+            reply_to: hdr.reply_to.map(str::to_string),
+            message_len: hdr.message_len,
+        }
+    }
+}
+
 // PUB <subject> [reply-to] <#bytes>\r\n[payload]\r\n
 #[derive(Debug)]
 pub struct PubHeader {
-    pub subject: String,
+    pub subject: Subject,
     pub reply_to: Option<String>,
     pub message_len: usize,
 }
 
+// HMSG <subject> <sid> [reply-to] <#header bytes> <#total bytes>\r\n[headers]\r\n\r\n[payload]\r\n
+#[derive(Debug)]
+pub struct HeaderMessageHeader<'a> {
+    pub subject: &'a str,
+    pub sid: usize,
+    pub reply_to: Option<&'a str>,
+    pub header_len: usize,
+    pub total_len: usize,
+}
+
+// HPUB <subject> [reply-to] <#header bytes> <#total bytes>\r\n[headers]\r\n\r\n[payload]\r\n
+#[derive(Debug)]
+pub struct HeaderPubHeader {
+    pub subject: Subject,
+    pub reply_to: Option<String>,
+    pub header_len: usize,
+    pub total_len: usize,
+}
+
+// NATS/1.0 [status] [description]
+#[derive(Debug)]
+pub struct HeaderVersionLine {
+    pub status: Option<u16>,
+    pub description: Option<String>,
+}
+
 // SUB <subject> [queue group] <sid>\r\n
 #[derive(Debug)]
 pub struct SubHeader {
-    pub subject: String,
+    pub subject: Subject,
     pub queue_group: Option<String>,
     pub sid: usize,
 }
@@ -44,75 +94,303 @@ pub struct ErrorHeader {
     pub message: String,
 }
 
-fn is_digit(chr: char) -> bool {
-    chr == '1'
-        || chr == '0'
-        || chr == '2'
-        || chr == '3'
-        || chr == '4'
-        || chr == '5'
-        || chr == '6'
-        || chr == '7'
-        || chr == '8'
-        || chr == '9'
+fn is_digit(chr: u8) -> bool {
+    chr.is_ascii_digit()
+}
+
+fn is_not_space(chr: u8) -> bool {
+    chr != b' ' && chr != b'\t' && chr != b'\r' && chr != b'\n'
+}
+
+fn is_not_crlf(chr: u8) -> bool {
+    chr != b'\r' && chr != b'\n'
+}
+
+fn is_not_tick(chr: u8) -> bool {
+    chr != b'\''
+}
+
+fn to_string(bytes: CompleteByteSlice) -> Result<String, str::Utf8Error> {
+    str::from_utf8(bytes.0).map(|s| s.to_string())
+}
+
+fn to_str(bytes: CompleteByteSlice<'_>) -> Result<&str, str::Utf8Error> {
+    str::from_utf8(bytes.0)
+}
+
+fn to_subject(bytes: CompleteByteSlice) -> Result<Subject, NatsParseError> {
+    Subject::from_bytes(bytes.0)
+}
+
+fn to_literal_str(bytes: CompleteByteSlice<'_>) -> Result<&str, NatsParseError> {
+    let subject = str::from_utf8(bytes.0).map_err(|e| {
+        NatsParseError::new(
+            ParseErrorKind::InvalidSubject,
+            "Invalid subject - not valid UTF-8",
+        )
+        .at(e.valid_up_to())
+    })?;
+    validate_literal(subject).map(|_| subject)
+}
+
+fn to_literal_subject(bytes: CompleteByteSlice) -> Result<Subject, NatsParseError> {
+    to_literal_str(bytes).map(Subject::from_validated)
+}
+
+// Splits a message into its control line and everything that follows the first CRLF. The
+// remainder is left intact so that payloads and header blocks (which may contain CRLFs of their
+// own) can be sliced by their declared lengths.
+pub fn split_control_line(source: &[u8]) -> Option<(&[u8], &[u8])> {
+    source
+        .windows(2)
+        .position(|w| w == b"\r\n")
+        .map(|idx| (&source[..idx], &source[idx + 2..]))
 }
 
-fn is_not_space(chr: char) -> bool {
-    chr != ' ' && chr != '\r' && chr != '\n'
+// Strips the verb (if present) and surrounding whitespace from a message carrying a JSON body,
+// such as INFO or CONNECT, returning the body along with the offset at which it starts
+pub fn json_body<'a>(buf: &'a [u8], verb: &[u8]) -> (usize, &'a [u8]) {
+    let start = if buf.starts_with(verb) { verb.len() } else { 0 };
+    let body = &buf[start..];
+    let leading = body.len() - body.trim_ascii_start().len();
+    (start + leading, body.trim_ascii())
 }
 
-fn is_not_tick(chr: char) -> bool {
-    chr != '\''
+// Splits a message into its control line and the offset at which its body begins
+pub fn control_line<'a>(buf: &'a [u8], verb: &str) -> Result<(&'a [u8], usize), NatsParseError> {
+    match split_control_line(buf) {
+        Some((control, _)) => Ok((control, control.len() + 2)),
+        None => Err(NatsParseError::new(
+            ParseErrorKind::Incomplete,
+            format!("Failed to parse {} message - no control line found", verb),
+        )
+        .with_verb(verb)
+        .at(buf.len())),
+    }
 }
 
-pub fn split_header_and_payload(source: &str) -> Option<(String, Vec<u8>)> {
-    let s: Vec<&str> = source.split("\r\n").collect();
-    if s.len() < 2 {
-        None
+// Takes exactly `len` bytes of payload starting at `start`, verifying that the payload is
+// followed by the terminating CRLF
+pub fn take_payload<'a>(
+    buf: &'a [u8],
+    start: usize,
+    len: usize,
+    verb: &str,
+) -> Result<&'a [u8], NatsParseError> {
+    // the declared size comes from the peer, so it may be large enough to overflow
+    let end = match start
+        .checked_add(len)
+        .filter(|e| e.checked_add(2).is_some())
+    {
+        Some(end) => end,
+        None => {
+            return Err(NatsParseError::new(
+                ParseErrorKind::PayloadSizeMismatch,
+                format!(
+                    "Failed to parse {} message - declared size of {} bytes is too large",
+                    verb, len
+                ),
+            )
+            .with_verb(verb)
+            .at(start))
+        }
+    };
+    if buf.len() < end + 2 {
+        Err(NatsParseError::new(
+            ParseErrorKind::Incomplete,
+            format!(
+                "Failed to parse {} message - expected {} bytes of payload",
+                verb, len
+            ),
+        )
+        .with_verb(verb)
+        .at(buf.len()))
+    } else if &buf[end..end + 2] != b"\r\n" {
+        Err(NatsParseError::new(
+            ParseErrorKind::PayloadSizeMismatch,
+            format!(
+                "Failed to parse {} message - payload does not match declared size of {} bytes",
+                verb, len
+            ),
+        )
+        .with_verb(verb)
+        .at(end))
     } else {
-        Some((s[0].to_string(), s[1].as_bytes().to_vec()))
+        Ok(&buf[start..end])
+    }
+}
+
+// Slices the body of an HPUB/HMSG starting at `start` into the header block and the payload
+// using the byte counts from the control line, verifying that the body is terminated by a CRLF
+pub fn split_headers_and_payload<'a>(
+    buf: &'a [u8],
+    start: usize,
+    header_len: usize,
+    total_len: usize,
+    verb: &str,
+) -> Result<(&'a [u8], &'a [u8]), NatsParseError> {
+    if header_len > total_len {
+        return Err(NatsParseError::new(
+            ParseErrorKind::MalformedControlLine,
+            format!(
+                "Failed to parse {} message - header size {} exceeds total size {}",
+                verb, header_len, total_len
+            ),
+        )
+        .with_verb(verb));
     }
+    take_payload(buf, start, total_len, verb).map(|b| (&b[..header_len], &b[header_len..]))
 }
 
-named!(parse_u64<::nom::types::CompleteStr, u64>,
-    flat_map!(take_while1_s!(is_digit), parse_to!(u64))
+// Converts the result of one of the nom parsers into a parse error carrying the offset at which
+// nom gave up
+fn finish<O>(
+    input: &[u8],
+    res: IResult<CompleteByteSlice, O>,
+    kind: ParseErrorKind,
+    verb: &str,
+) -> Result<O, NatsParseError> {
+    let offset = match res {
+        Ok((_, o)) => return Ok(o),
+        Err(nom::Err::Error(ctx)) | Err(nom::Err::Failure(ctx)) => match ctx {
+            Context::Code(rest, _) => input.len() - rest.0.len(),
+            #[allow(unreachable_patterns)]
+            _ => 0,
+        },
+        Err(nom::Err::Incomplete(_)) => input.len(),
+    };
+    let what = match kind {
+        ParseErrorKind::MalformedHeaders => "header version line",
+        _ => "control line",
+    };
+    Err(NatsParseError::new(
+        kind,
+        format!(
+            "Failed to parse {} message - malformed {} '{}'",
+            verb,
+            what,
+            String::from_utf8_lossy(input)
+        ),
+    )
+    .with_verb(verb)
+    .at(offset))
+}
+
+named!(parse_u64<CompleteByteSlice, u64>,
+    flat_map!(take_while1!(is_digit), parse_to!(u64))
+);
+
+named!(parse_completestr<CompleteByteSlice, String>, map_res!(
+    take_while1!(is_not_space),
+    to_string
+));
+
+named!(
+    parse_str<CompleteByteSlice<'_>, &str>,
+    map_res!(take_while1!(is_not_space), to_str)
+);
+
+// subjects of messages being published or delivered may not contain wildcards
+named!(parse_literal_subject<CompleteByteSlice, Subject>, map_res!(
+    take_while1!(is_not_space),
+    to_literal_subject
+));
+
+named!(
+    parse_literal_str<CompleteByteSlice<'_>, &str>,
+    map_res!(take_while1!(is_not_space), to_literal_str)
 );
 
-named!(parse_completestr<::nom::types::CompleteStr, String >, map!(
-    take_while1_s!(is_not_space),
-    |r|r.to_string()
+named!(parse_subject<CompleteByteSlice, Subject>, map_res!(
+    take_while1!(is_not_space),
+    to_subject
+));
+
+named!(parse_alpha<CompleteByteSlice, String>, map_res!(
+    take_while1!(is_not_tick),
+    to_string
 ));
 
-named!(parse_alpha<CompleteStr, String>, map!(
-    take_while1_s!(is_not_tick),
-    |r|r.to_string()
+named!(parse_line<CompleteByteSlice, String>, map!(
+    map_res!(take_while1!(is_not_crlf), to_string),
+    |r| r.trim().to_string()
 ));
 
 named!(spec_whitespace, eat_separator!(&b" \t"[..]));
 
-named!(msg_header<::nom::types::CompleteStr, MessageHeader>,
+named!(msg_header<CompleteByteSlice, MessageHeaderRef>,
     do_parse!(
-        tag_s!("MSG")                           >>
+        tag!("MSG")                           >>
         is_a!(" \t")                            >>
-        subject: parse_completestr              >>                
+        subject: parse_literal_str              >>
         is_a!(" \t")                            >>
-        sid:  parse_u64                         >>                
+        sid:  parse_u64                         >>
         is_a!(" \t")                            >>
-        reply_to: opt!(terminated!(parse_completestr, is_a!(" \t"))) >>
+        reply_to: opt!(terminated!(parse_str, is_a!(" \t"))) >>
         message_len: parse_u64                  >>
 
-        ( MessageHeader { sid: sid as usize, subject, reply_to, message_len: message_len as usize } )
+        ( MessageHeaderRef { sid: sid as usize, subject, reply_to, message_len: message_len as usize } )
     )
 );
-pub fn parse_msg_header(header: &str) -> Option<MessageHeader> {
-    msg_header(CompleteStr(header)).ok().map(|h| h.1)
+pub fn parse_msg_header<T: AsRef<[u8]> + ?Sized>(
+    header: &T,
+) -> Result<MessageHeader, NatsParseError> {
+    parse_msg_header_ref(header.as_ref()).map(MessageHeader::from)
+}
+
+pub fn parse_msg_header_ref(header: &[u8]) -> Result<MessageHeaderRef<'_>, NatsParseError> {
+    finish(
+        header,
+        msg_header(CompleteByteSlice(header)),
+        ParseErrorKind::MalformedControlLine,
+        "MSG",
+    )
 }
 
-named!(pub_header<CompleteStr, PubHeader>,
+named!(header_sizes<CompleteByteSlice, (u64, u64)>,
     do_parse!(
-        tag_s!("PUB")                               >>
+        header_len: parse_u64                   >>
+        is_a!(" \t")                            >>
+        total_len: parse_u64                    >>
+
+        ( (header_len, total_len) )
+    )
+);
+
+named!(hmsg_header<CompleteByteSlice, HeaderMessageHeader>,
+    do_parse!(
+        tag!("HMSG")                            >>
+        is_a!(" \t")                            >>
+        subject: parse_literal_str              >>
+        is_a!(" \t")                            >>
+        sid: parse_u64                          >>
+        is_a!(" \t")                            >>
+        reply_to: opt!(terminated!(parse_str, terminated!(is_a!(" \t"), peek!(header_sizes)))) >>
+        sizes: header_sizes                     >>
+
+        ( HeaderMessageHeader {
+            subject,
+            sid: sid as usize,
+            reply_to,
+            header_len: sizes.0 as usize,
+            total_len: sizes.1 as usize,
+        } )
+    )
+);
+pub fn parse_hmsg_header(header: &[u8]) -> Result<HeaderMessageHeader<'_>, NatsParseError> {
+    finish(
+        header,
+        hmsg_header(CompleteByteSlice(header)),
+        ParseErrorKind::MalformedControlLine,
+        "HMSG",
+    )
+}
+
+named!(pub_header<CompleteByteSlice, PubHeader>,
+    do_parse!(
+        tag!("PUB")                               >>
         is_a!(" \t")                                >>
-        subject: parse_completestr                  >>
+        subject: parse_literal_subject              >>
         is_a!(" \t")                                >>
         reply_to: opt!(terminated!(parse_completestr, is_a!(" \t"))) >>
         message_len: parse_u64                      >>
@@ -120,15 +398,88 @@ named!(pub_header<CompleteStr, PubHeader>,
         ( PubHeader { subject, reply_to, message_len: message_len as usize } )
     )
 );
-pub fn parse_pub_header(header: &str) -> Option<PubHeader> {
-    pub_header(CompleteStr(header)).ok().map(|h| h.1)
+pub fn parse_pub_header(header: &[u8]) -> Result<PubHeader, NatsParseError> {
+    finish(
+        header,
+        pub_header(CompleteByteSlice(header)),
+        ParseErrorKind::MalformedControlLine,
+        "PUB",
+    )
+}
+
+named!(hpub_header<CompleteByteSlice, HeaderPubHeader>,
+    do_parse!(
+        tag!("HPUB")                                >>
+        is_a!(" \t")                                >>
+        subject: parse_literal_subject              >>
+        is_a!(" \t")                                >>
+        reply_to: opt!(terminated!(parse_completestr, terminated!(is_a!(" \t"), peek!(header_sizes)))) >>
+        sizes: header_sizes                         >>
+
+        ( HeaderPubHeader {
+            subject,
+            reply_to,
+            header_len: sizes.0 as usize,
+            total_len: sizes.1 as usize,
+        } )
+    )
+);
+pub fn parse_hpub_header(header: &[u8]) -> Result<HeaderPubHeader, NatsParseError> {
+    finish(
+        header,
+        hpub_header(CompleteByteSlice(header)),
+        ParseErrorKind::MalformedControlLine,
+        "HPUB",
+    )
+}
+
+named!(header_version_line<CompleteByteSlice, (Option<CompleteByteSlice>, Option<String>)>,
+    do_parse!(
+        tag!("NATS/1.0")                                    >>
+        status: opt!(preceded!(is_a!(" \t"), take_while1!(is_digit))) >>
+        description: opt!(preceded!(is_a!(" \t"), parse_line)) >>
+        opt!(is_a!(" \t"))                                  >>
+        eof!()                                              >>
+
+        ( (status, description.filter(|d| !d.is_empty())) )
+    )
+);
+pub fn parse_header_version_line(line: &[u8]) -> Result<HeaderVersionLine, NatsParseError> {
+    let (status, description) = finish(
+        line,
+        header_version_line(CompleteByteSlice(line)),
+        ParseErrorKind::MalformedHeaders,
+        "NATS/1.0",
+    )?;
+    let status = match status {
+        // status codes are always three digits
+        Some(digits) if digits.0.len() == 3 => {
+            Some(digits.0.iter().fold(0, |n, d| n * 10 + u16::from(d - b'0')))
+        }
+        Some(digits) => {
+            return Err(NatsParseError::new(
+                ParseErrorKind::MalformedHeaders,
+                format!(
+                    "Failed to parse NATS/1.0 message - invalid status '{}'",
+                    String::from_utf8_lossy(digits.0)
+                ),
+            )
+            .with_verb("NATS/1.0")
+            .at(digits.0.as_ptr() as usize - line.as_ptr() as usize))
+        }
+        None => None,
+    };
+    Ok(HeaderVersionLine {
+        status,
+        description,
+    })
 }
 
-named!(sub_header<CompleteStr, SubHeader>,
+named!(sub_header<CompleteByteSlice, SubHeader>,
     do_parse!(
-        tag_s!("SUB")                                   >>
+        tag!("SUB")                                   >>
         is_a!(" \t")                                    >>
-        subject: parse_completestr                      >>
+        subject: parse_subject                          >>
         is_a!(" \t")                                    >>
         queue_group: opt!(terminated!(parse_completestr, is_a!(" \t"))) >>
         sid: parse_u64                                  >>
@@ -137,13 +488,18 @@ named!(sub_header<CompleteStr, SubHeader>,
     )
 );
 
-pub fn parse_sub_header(header: &str) -> Option<SubHeader> {
-    sub_header(CompleteStr(header)).ok().map(|h| h.1)
+pub fn parse_sub_header(header: &[u8]) -> Result<SubHeader, NatsParseError> {
+    finish(
+        header,
+        sub_header(CompleteByteSlice(header)),
+        ParseErrorKind::MalformedControlLine,
+        "SUB",
+    )
 }
 
-named!(unsub_header<CompleteStr, UnsubHeader>,
+named!(unsub_header<CompleteByteSlice, UnsubHeader>,
     do_parse!(
-        tag_s!("UNSUB")                 >>
+        tag!("UNSUB")                 >>
         is_a!(" \t")                    >>
         sid: parse_u64                  >>
         opt!(is_a!(" \t"))              >>
@@ -152,41 +508,53 @@ named!(unsub_header<CompleteStr, UnsubHeader>,
         ( UnsubHeader { sid: sid as usize, max_messages: max_messages.map(|m| m as usize) })
     )
 );
-pub fn parse_unsub_header(header: &str) -> Option<UnsubHeader> {
-    unsub_header(CompleteStr(header)).ok().map(|h| h.1)
+pub fn parse_unsub_header(header: &[u8]) -> Result<UnsubHeader, NatsParseError> {
+    finish(
+        header,
+        unsub_header(CompleteByteSlice(header)),
+        ParseErrorKind::MalformedControlLine,
+        "UNSUB",
+    )
 }
 
-named!(err_header<CompleteStr, ErrorHeader>,
+named!(err_header<CompleteByteSlice, ErrorHeader>,
     do_parse!(
-        tag_s!("-ERR '") >>
+        tag!("-ERR '") >>
         message: parse_alpha >>
         char!('\'') >>
 
         ( ErrorHeader { message } )
     )
 );
-pub fn parse_err_header(header: &str) -> Option<ErrorHeader> {
-    err_header(CompleteStr(header)).ok().map(|h| h.1)
+pub fn parse_err_header(header: &[u8]) -> Result<ErrorHeader, NatsParseError> {
+    finish(
+        header,
+        err_header(CompleteByteSlice(header)),
+        ParseErrorKind::MalformedControlLine,
+        "-ERR",
+    )
 }
 
 #[cfg(test)]
 mod test {
     use super::{
-        err_header, msg_header, pub_header, split_header_and_payload, sub_header, unsub_header,
+        err_header, hmsg_header, hpub_header, msg_header, parse_header_version_line,
+        parse_msg_header, parse_pub_header, parse_sub_header, pub_header, split_control_line,
+        split_headers_and_payload, sub_header, take_payload, unsub_header,
     };
-    use nom::types::CompleteStr;
+    use crate::ParseErrorKind;
+    use nom::types::CompleteByteSlice;
 
     #[test]
     fn msg_reply_to() {
         let raw = "MSG workdispatch 1 reply.topic 11\r\nHello World\r\n";
-        let split = split_header_and_payload(raw);
+        let split = split_control_line(raw.as_bytes());
         assert!(split.is_some());
-        if let Some(split) = split {
-            let hdr = split.0;
-            let payload = split.1;
+        if let Some((hdr, _)) = split {
+            let payload = take_payload(raw.as_bytes(), hdr.len() + 2, 11, "MSG").unwrap();
 
-            assert_eq!(String::from_utf8(payload).unwrap(), "Hello World");
-            let res = msg_header(CompleteStr(&hdr));
+            assert_eq!(payload, b"Hello World");
+            let res = msg_header(CompleteByteSlice(hdr));
             println!("{:?}", res);
             assert!(res.is_ok());
         }
@@ -195,14 +563,13 @@ mod test {
     #[test]
     fn msg_irreg_whitespace() {
         let raw = "MSG  \t  workdispatch 1 reply.topic 11\r\nHello World\r\n";
-        let split = split_header_and_payload(raw);
+        let split = split_control_line(raw.as_bytes());
         assert!(split.is_some());
-        if let Some(split) = split {
-            let hdr = split.0;
-            let payload = split.1;
+        if let Some((hdr, _)) = split {
+            let payload = take_payload(raw.as_bytes(), hdr.len() + 2, 11, "MSG").unwrap();
 
-            assert_eq!(String::from_utf8(payload).unwrap(), "Hello World");
-            let res = msg_header(CompleteStr(&hdr));
+            assert_eq!(payload, b"Hello World");
+            let res = msg_header(CompleteByteSlice(hdr));
             assert!(res.is_ok());
         }
     }
@@ -210,7 +577,7 @@ mod test {
     #[test]
     fn unsub_no_max() {
         let msg = "UNSUB 1";
-        let res = unsub_header(CompleteStr(msg));
+        let res = unsub_header(CompleteByteSlice(msg.as_bytes()));
         assert!(res.is_ok());
         if let Ok(header) = res {
             assert_eq!(header.1.sid, 1);
@@ -221,7 +588,7 @@ mod test {
     #[test]
     fn unsub_max() {
         let msg = "UNSUB 1 5";
-        let res = unsub_header(CompleteStr(msg));
+        let res = unsub_header(CompleteByteSlice(msg.as_bytes()));
         assert!(res.is_ok());
         if let Ok(header) = res {
             assert_eq!(header.1.sid, 1);
@@ -232,7 +599,7 @@ mod test {
     #[test]
     fn pub_no_reply() {
         let msg = "PUB FOO 11";
-        let res = pub_header(CompleteStr(msg));
+        let res = pub_header(CompleteByteSlice(msg.as_bytes()));
         assert!(res.is_ok());
         if let Ok(header) = res {
             assert_eq!(header.1.subject, "FOO");
@@ -243,7 +610,7 @@ mod test {
     #[test]
     fn pub_reply() {
         let msg = "PUB FRONT.DOOR INBOX.22 11";
-        let res = pub_header(CompleteStr(msg));
+        let res = pub_header(CompleteByteSlice(msg.as_bytes()));
         assert!(res.is_ok());
         if let Ok(header) = res {
             assert_eq!(header.1.subject, "FRONT.DOOR");
@@ -254,7 +621,7 @@ mod test {
     #[test]
     fn sub_no_qg() {
         let msg = "SUB FOO 1";
-        let res = sub_header(CompleteStr(&msg));
+        let res = sub_header(CompleteByteSlice(msg.as_bytes()));
         assert!(res.is_ok());
         if let Ok(header) = res {
             assert_eq!(header.1.subject, "FOO");
@@ -266,7 +633,7 @@ mod test {
     #[test]
     fn sub_qg() {
         let msg = "SUB BAR G1 44";
-        let res = sub_header(CompleteStr(&msg));
+        let res = sub_header(CompleteByteSlice(msg.as_bytes()));
         assert!(res.is_ok());
         if let Ok(header) = res {
             assert_eq!(header.1.subject, "BAR");
@@ -278,7 +645,7 @@ mod test {
     #[test]
     fn msg_no_reply() {
         let msg = "MSG workdispatch 1 11";
-        let res = msg_header(CompleteStr(msg));
+        let res = msg_header(CompleteByteSlice(msg.as_bytes()));
         println!("{:?}", res);
         assert!(res.is_ok());
     }
@@ -286,11 +653,151 @@ mod test {
     #[test]
     fn error_header() {
         let msg = "-ERR 'Attempted To Connect To Route Port'";
-        let res = err_header(CompleteStr(msg));
+        let res = err_header(CompleteByteSlice(msg.as_bytes()));
         println!("{:?}", res);
         assert!(res.is_ok());
         if let Ok(header) = res {
             assert_eq!(header.1.message, "Attempted To Connect To Route Port");
         }
     }
+
+    #[test]
+    fn hpub_no_reply() {
+        let msg = "HPUB FOO 22 33";
+        let res = hpub_header(CompleteByteSlice(msg.as_bytes()));
+        assert!(res.is_ok());
+        if let Ok(header) = res {
+            assert_eq!(header.1.subject, "FOO");
+            assert!(header.1.reply_to.is_none());
+            assert_eq!(header.1.header_len, 22);
+            assert_eq!(header.1.total_len, 33);
+        }
+    }
+
+    #[test]
+    fn hpub_reply() {
+        let msg = "HPUB FRONT.DOOR  \t INBOX.22 22 33";
+        let res = hpub_header(CompleteByteSlice(msg.as_bytes()));
+        assert!(res.is_ok());
+        if let Ok(header) = res {
+            assert_eq!(header.1.subject, "FRONT.DOOR");
+            assert_eq!(header.1.reply_to, Some("INBOX.22".to_string()));
+            assert_eq!(header.1.header_len, 22);
+            assert_eq!(header.1.total_len, 33);
+        }
+    }
+
+    #[test]
+    fn hmsg_no_reply() {
+        let msg = "HMSG workdispatch 1 22 33";
+        let res = hmsg_header(CompleteByteSlice(msg.as_bytes()));
+        assert!(res.is_ok());
+        if let Ok(header) = res {
+            assert_eq!(header.1.subject, "workdispatch");
+            assert_eq!(header.1.sid, 1);
+            assert!(header.1.reply_to.is_none());
+            assert_eq!(header.1.header_len, 22);
+            assert_eq!(header.1.total_len, 33);
+        }
+    }
+
+    #[test]
+    fn hmsg_reply_to() {
+        let msg = "HMSG workdispatch 1 reply.topic 22 33";
+        let res = hmsg_header(CompleteByteSlice(msg.as_bytes()));
+        assert!(res.is_ok());
+        if let Ok(header) = res {
+            assert_eq!(header.1.sid, 1);
+            assert_eq!(header.1.reply_to, Some("reply.topic"));
+            assert_eq!(header.1.header_len, 22);
+            assert_eq!(header.1.total_len, 33);
+        }
+    }
+
+    #[test]
+    fn headers_and_payload_by_length() {
+        let body = b"NATS/1.0\r\nA: B\r\n\r\nHello\r\n";
+        let split = split_headers_and_payload(body, 0, 18, 23, "HMSG");
+        assert!(split.is_ok());
+        if let Ok((headers, payload)) = split {
+            assert_eq!(headers, b"NATS/1.0\r\nA: B\r\n\r\n");
+            assert_eq!(payload, b"Hello");
+        }
+        let err = split_headers_and_payload(body, 0, 18, 22, "HMSG").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::PayloadSizeMismatch);
+        assert_eq!(err.offset(), 22);
+        let err = split_headers_and_payload(body, 0, 24, 23, "HMSG").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::MalformedControlLine);
+    }
+
+    #[test]
+    fn version_line_status() {
+        let line = parse_header_version_line(b"NATS/1.0").unwrap();
+        assert_eq!(line.status, None);
+        assert_eq!(line.description, None);
+
+        let line = parse_header_version_line(b"NATS/1.0 503").unwrap();
+        assert_eq!(line.status, Some(503));
+        assert_eq!(line.description, None);
+
+        let line = parse_header_version_line(b"NATS/1.0 100 Idle Heartbeat").unwrap();
+        assert_eq!(line.status, Some(100));
+        assert_eq!(line.description, Some("Idle Heartbeat".to_string()));
+
+        assert!(parse_header_version_line(b"NATS/2.0").is_err());
+        let err = parse_header_version_line(b"NATS/1.0 70000").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::MalformedHeaders);
+        assert_eq!(err.offset(), 9);
+    }
+
+    #[test]
+    fn payload_by_length() {
+        let body = b"Hello\r\nWorld\r\n";
+        assert_eq!(take_payload(body, 0, 12, "MSG").unwrap(), b"Hello\r\nWorld");
+        assert_eq!(take_payload(body, 7, 5, "MSG").unwrap(), b"World");
+        assert_eq!(
+            take_payload(body, 0, 6, "MSG").unwrap_err().kind(),
+            ParseErrorKind::PayloadSizeMismatch
+        );
+        assert!(take_payload(body, 0, 13, "MSG")
+            .unwrap_err()
+            .is_incomplete());
+        assert_eq!(take_payload(b"\r\n", 0, 0, "MSG").unwrap(), b"");
+    }
+
+    #[test]
+    fn payload_length_overflow() {
+        let body = b"Hello\r\n";
+        for len in &[usize::MAX, usize::MAX - 7, usize::MAX - 8] {
+            let err = take_payload(body, 7, *len, "MSG").unwrap_err();
+            assert_eq!(err.kind(), ParseErrorKind::PayloadSizeMismatch);
+        }
+        let err = crate::DeliveredMessage::parse(b"MSG a 1 18446744073709551615\r\nHello\r\n")
+            .unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::PayloadSizeMismatch);
+    }
+
+    #[test]
+    fn control_line_error_offset() {
+        let err = parse_msg_header("MSG FOO 1 x").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::MalformedControlLine);
+        assert_eq!(err.verb(), Some("MSG"));
+        assert_eq!(err.offset(), 10);
+
+        let err = parse_sub_header(b"SUB").unwrap_err();
+        assert_eq!(err.verb(), Some("SUB"));
+        assert_eq!(err.offset(), 3);
+    }
+
+    #[test]
+    fn subjects_are_validated() {
+        let sub = parse_sub_header(b"SUB orders.*.> 1").unwrap();
+        assert!(sub.subject.is_wildcard());
+
+        let err = parse_msg_header("MSG orders.* 1 5").unwrap_err();
+        assert_eq!(err.kind(), ParseErrorKind::MalformedControlLine);
+        assert_eq!(err.offset(), 4);
+        assert!(parse_pub_header(b"PUB orders..new 5").is_err());
+        assert!(parse_sub_header(b"SUB orders.>.new 1").is_err());
+    }
 }
@@ -0,0 +1,338 @@
+use crate::parser;
+use crate::NatsParseError;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+/// An error reported by the server with an `-ERR` message. The documented error strings are
+/// classified into a `ServerErrorKind` so that clients can decide how to react without
+/// comparing text, and the text is kept exactly as the server sent it. The text is not
+/// escaped on the wire, so a message containing `'` ends at its first `'` when parsed and does
+/// not survive an encode and parse round trip.
+///
+/// ```rust
+/// extern crate nats_types;
+///
+/// use nats_types::{ServerError, ServerErrorKind};
+///
+/// let err = ServerError::parse(b"-ERR 'Stale Connection'\r\n").unwrap();
+/// assert_eq!(err.kind(), &ServerErrorKind::StaleConnection);
+/// assert_eq!(err.message(), "Stale Connection");
+/// assert!(err.is_fatal());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ServerError {
+    kind: ServerErrorKind,
+    message: String,
+}
+
+/// The classification of a `ServerError`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ServerErrorKind {
+    /// `Unknown Protocol Operation`
+    UnknownProtocolOperation,
+    /// `Attempted To Connect To Route Port`
+    AttemptedToConnectToRoutePort,
+    /// `Authorization Violation`
+    AuthorizationViolation,
+    /// `Authorization Timeout`
+    AuthorizationTimeout,
+    /// `Invalid Client Protocol`
+    InvalidClientProtocol,
+    /// `Maximum Control Line Exceeded`
+    MaximumControlLineExceeded,
+    /// `Parser Error`
+    ParserError,
+    /// `Secure Connection - TLS Required`
+    TlsRequired,
+    /// `Stale Connection`
+    StaleConnection,
+    /// `Maximum Connections Exceeded`
+    MaximumConnectionsExceeded,
+    /// `Maximum Subscriptions Exceeded`
+    MaximumSubscriptionsExceeded,
+    /// `Slow Consumer`
+    SlowConsumer,
+    /// `Maximum Payload Violation`
+    MaximumPayloadViolation,
+    /// `User Authentication Expired`
+    UserAuthenticationExpired,
+    /// `Account Authentication Expired`
+    AccountAuthenticationExpired,
+    /// `Authentication Revoked`
+    AuthenticationRevoked,
+    /// `Invalid Subject`
+    InvalidSubject,
+    /// `Permissions Violation for Publish to <subject>`
+    PublishPermissionsViolation { subject: String },
+    /// `Permissions Violation for Publish with Reply of <subject>`
+    ReplyPermissionsViolation { subject: String },
+    /// `Permissions Violation for Subscription to <subject>`, optionally followed by
+    /// `using queue <queue group>`
+    SubscriptionPermissionsViolation {
+        subject: String,
+        queue_group: Option<String>,
+    },
+    /// An error string not recognized by this crate
+    Other,
+}
+
+const PUBLISH_VIOLATION: &str = "Permissions Violation for Publish to ";
+const REPLY_VIOLATION: &str = "Permissions Violation for Publish with Reply of ";
+const SUBSCRIPTION_VIOLATION: &str = "Permissions Violation for Subscription to ";
+const USING_QUEUE: &str = " using queue ";
+
+const FIXED_MESSAGES: &[(&str, ServerErrorKind)] = &[
+    (
+        "Unknown Protocol Operation",
+        ServerErrorKind::UnknownProtocolOperation,
+    ),
+    (
+        "Attempted To Connect To Route Port",
+        ServerErrorKind::AttemptedToConnectToRoutePort,
+    ),
+    (
+        "Authorization Violation",
+        ServerErrorKind::AuthorizationViolation,
+    ),
+    (
+        "Authorization Timeout",
+        ServerErrorKind::AuthorizationTimeout,
+    ),
+    (
+        "Invalid Client Protocol",
+        ServerErrorKind::InvalidClientProtocol,
+    ),
+    (
+        "Maximum Control Line Exceeded",
+        ServerErrorKind::MaximumControlLineExceeded,
+    ),
+    ("Parser Error", ServerErrorKind::ParserError),
+    (
+        "Secure Connection - TLS Required",
+        ServerErrorKind::TlsRequired,
+    ),
+    ("Stale Connection", ServerErrorKind::StaleConnection),
+    (
+        "Maximum Connections Exceeded",
+        ServerErrorKind::MaximumConnectionsExceeded,
+    ),
+    (
+        "Maximum Subscriptions Exceeded",
+        ServerErrorKind::MaximumSubscriptionsExceeded,
+    ),
+    ("Slow Consumer", ServerErrorKind::SlowConsumer),
+    (
+        "Maximum Payload Violation",
+        ServerErrorKind::MaximumPayloadViolation,
+    ),
+    (
+        "User Authentication Expired",
+        ServerErrorKind::UserAuthenticationExpired,
+    ),
+    (
+        "Account Authentication Expired",
+        ServerErrorKind::AccountAuthenticationExpired,
+    ),
+    (
+        "Authentication Revoked",
+        ServerErrorKind::AuthenticationRevoked,
+    ),
+    ("Invalid Subject", ServerErrorKind::InvalidSubject),
+];
+
+impl ServerError {
+    /// Parses a `-ERR` control line into a classified error
+    pub fn parse(buf: &[u8]) -> Result<ServerError, NatsParseError> {
+        parser::parse_err_header(buf).map(|h| ServerError::from_message(&h.message))
+    }
+
+    /// Classifies the text of an error as it appears between the quotes of an `-ERR` message.
+    /// The documented strings are recognized regardless of case and surrounding whitespace.
+    pub fn from_message(message: &str) -> ServerError {
+        let trimmed = message.trim();
+        let kind = FIXED_MESSAGES
+            .iter()
+            .find(|(text, _)| text.eq_ignore_ascii_case(trimmed))
+            .map(|(_, kind)| kind.clone())
+            .or_else(|| permissions_violation(trimmed))
+            .unwrap_or(ServerErrorKind::Other);
+        ServerError {
+            kind,
+            message: message.to_string(),
+        }
+    }
+
+    /// The classification of the error
+    pub fn kind(&self) -> &ServerErrorKind {
+        &self.kind
+    }
+
+    /// The text of the error exactly as sent by the server
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Indicates whether the server closes the connection after sending this error. Only
+    /// `Invalid Subject`, `Maximum Subscriptions Exceeded` and permission violations leave the
+    /// connection open; unrecognized errors are assumed not to be fatal, as the client will
+    /// observe the connection closing if they are.
+    pub fn is_fatal(&self) -> bool {
+        !matches!(
+            self.kind,
+            ServerErrorKind::InvalidSubject
+                | ServerErrorKind::MaximumSubscriptionsExceeded
+                | ServerErrorKind::PublishPermissionsViolation { .. }
+                | ServerErrorKind::ReplyPermissionsViolation { .. }
+                | ServerErrorKind::SubscriptionPermissionsViolation { .. }
+                | ServerErrorKind::Other
+        )
+    }
+
+    /// The subject named by a permissions violation
+    pub fn subject(&self) -> Option<&str> {
+        match &self.kind {
+            ServerErrorKind::PublishPermissionsViolation { subject }
+            | ServerErrorKind::ReplyPermissionsViolation { subject }
+            | ServerErrorKind::SubscriptionPermissionsViolation { subject, .. } => Some(subject),
+            _ => None,
+        }
+    }
+}
+
+// The server formats subjects with Go's %q, so they arrive surrounded by double quotes.
+// Anything else, such as a subject containing escaped characters, is left as `Other`.
+fn permissions_violation(message: &str) -> Option<ServerErrorKind> {
+    if let Some(rest) = message.strip_prefix(PUBLISH_VIOLATION) {
+        Some(ServerErrorKind::PublishPermissionsViolation {
+            subject: unquote(rest)?,
+        })
+    } else if let Some(rest) = message.strip_prefix(REPLY_VIOLATION) {
+        Some(ServerErrorKind::ReplyPermissionsViolation {
+            subject: unquote(rest)?,
+        })
+    } else if let Some(rest) = message.strip_prefix(SUBSCRIPTION_VIOLATION) {
+        let (subject, queue_group) = match rest.find(USING_QUEUE) {
+            Some(pos) => (&rest[..pos], Some(&rest[pos + USING_QUEUE.len()..])),
+            None => (rest, None),
+        };
+        Some(ServerErrorKind::SubscriptionPermissionsViolation {
+            subject: unquote(subject)?,
+            queue_group: match queue_group {
+                Some(q) => Some(unquote(q)?),
+                None => None,
+            },
+        })
+    } else {
+        None
+    }
+}
+
+fn unquote(s: &str) -> Option<String> {
+    let inner = s.strip_prefix('"')?.strip_suffix('"')?;
+    if inner.is_empty() || inner.contains(|c: char| c == '"' || c.is_whitespace()) {
+        None
+    } else {
+        Some(inner.to_string())
+    }
+}
+
+impl From<&str> for ServerError {
+    fn from(message: &str) -> Self {
+        ServerError::from_message(message)
+    }
+}
+
+impl std::error::Error for ServerError {}
+
+impl Display for ServerError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ServerError, ServerErrorKind};
+    use crate::Encode;
+
+    #[test]
+    fn classify_documented_errors() {
+        let err = ServerError::parse(b"-ERR 'Stale Connection'\r\n").unwrap();
+        assert_eq!(err.kind(), &ServerErrorKind::StaleConnection);
+        assert!(err.is_fatal());
+        assert_eq!(err.message(), "Stale Connection");
+
+        let err = ServerError::from("Maximum Payload Violation");
+        assert_eq!(err.kind(), &ServerErrorKind::MaximumPayloadViolation);
+        assert!(err.is_fatal());
+
+        let err = ServerError::from("Invalid Subject");
+        assert!(!err.is_fatal());
+
+        let err = ServerError::from("maximum subscriptions exceeded");
+        assert_eq!(err.kind(), &ServerErrorKind::MaximumSubscriptionsExceeded);
+        assert!(!err.is_fatal());
+        assert_eq!(err.message(), "maximum subscriptions exceeded");
+
+        let err = ServerError::from("Something New");
+        assert_eq!(err.kind(), &ServerErrorKind::Other);
+        assert!(!err.is_fatal());
+        assert_eq!(err.to_string(), "Something New");
+    }
+
+    #[test]
+    fn quote_in_message() {
+        let err = ServerError::from("Can't do that");
+        let encoded = String::from_utf8(err.encode().unwrap()).unwrap();
+        assert_eq!(encoded, "-ERR 'Can't do that'\r\n");
+        let parsed = ServerError::parse(encoded.as_bytes()).unwrap();
+        assert_eq!(parsed.message(), "Can");
+        assert_ne!(parsed, err);
+    }
+
+    #[test]
+    fn permissions_violations() {
+        let raw = "Permissions Violation for Publish to \"foo.bar\"";
+        let err = ServerError::from(raw);
+        assert_eq!(
+            err.kind(),
+            &ServerErrorKind::PublishPermissionsViolation {
+                subject: "foo.bar".to_string()
+            }
+        );
+        assert_eq!(err.subject(), Some("foo.bar"));
+        assert!(!err.is_fatal());
+        assert_eq!(err.message(), raw);
+
+        let raw = "Permissions Violation for Publish with Reply of \"_INBOX.abc\"";
+        let err = ServerError::parse(format!("-ERR '{}'\r\n", raw).as_bytes()).unwrap();
+        assert_eq!(
+            err.kind(),
+            &ServerErrorKind::ReplyPermissionsViolation {
+                subject: "_INBOX.abc".to_string()
+            }
+        );
+        assert_eq!(err.subject(), Some("_INBOX.abc"));
+        assert!(!err.is_fatal());
+        assert_eq!(err.message(), raw);
+
+        let raw = "Permissions Violation for Subscription to \"foo.>\" using queue \"workers\"";
+        let err = ServerError::from(raw);
+        assert_eq!(
+            err.kind(),
+            &ServerErrorKind::SubscriptionPermissionsViolation {
+                subject: "foo.>".to_string(),
+                queue_group: Some("workers".to_string()),
+            }
+        );
+        assert_eq!(err.message(), raw);
+
+        // text that does not match the server's format is kept as is
+        let raw = "Permissions Violation for Subscription to foo.*";
+        let err = ServerError::from(raw);
+        assert_eq!(err.kind(), &ServerErrorKind::Other);
+        assert_eq!(err.message(), raw);
+    }
+}
@@ -0,0 +1,423 @@
+use crate::{NatsParseError, ParseErrorKind};
+use std::borrow::Borrow;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+/// A subject that follows the NATS subject rules: it is made up of one or more non-empty
+/// tokens separated by `.`, and contains no whitespace. A token consisting of just `*` matches
+/// any single token and a final token of `>` matches one or more tokens; wildcards are only
+/// meaningful in subscriptions. A `*` or `>` within a longer token is an ordinary character.
+///
+/// ```rust
+/// extern crate nats_types;
+///
+/// use nats_types::Subject;
+///
+/// let subject: Subject = "orders.*.shipped".parse().unwrap();
+/// assert_eq!(subject.tokens().collect::<Vec<_>>(), ["orders", "*", "shipped"]);
+/// assert!(subject.is_wildcard());
+/// assert!("orders..shipped".parse::<Subject>().is_err());
+/// assert!("orders.>.shipped".parse::<Subject>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Subject(String);
+
+impl Subject {
+    /// Validates the given string as a subject
+    pub fn new<S: Into<String>>(subject: S) -> Result<Subject, NatsParseError> {
+        let subject = subject.into();
+        validate(&subject)?;
+        Ok(Subject(subject))
+    }
+
+    /// Validates the given string as a subject that may be published to, i.e. one that
+    /// contains no wildcards
+    pub fn literal<S: Into<String>>(subject: S) -> Result<Subject, NatsParseError> {
+        let subject = subject.into();
+        validate_literal(&subject)?;
+        Ok(Subject(subject))
+    }
+
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<Subject, NatsParseError> {
+        match std::str::from_utf8(bytes) {
+            Ok(s) => Subject::new(s),
+            Err(e) => Err(NatsParseError::new(
+                ParseErrorKind::InvalidSubject,
+                "Invalid subject - not valid UTF-8",
+            )
+            .at(e.valid_up_to())),
+        }
+    }
+
+    // Wraps a subject that has already been validated
+    pub(crate) fn from_validated<S: Into<String>>(subject: S) -> Subject {
+        Subject(subject.into())
+    }
+
+    /// The subject as a string slice
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Consumes the subject, returning the underlying string
+    pub fn into_string(self) -> String {
+        self.0
+    }
+
+    /// An iterator over the `.`-separated tokens of the subject
+    pub fn tokens(&self) -> Tokens<'_> {
+        Tokens(self.0.split('.'))
+    }
+
+    /// Indicates whether any token of the subject is a wildcard
+    pub fn is_wildcard(&self) -> bool {
+        self.tokens().any(|t| t == "*" || t == ">")
+    }
+
+    /// Indicates whether the given subject would be delivered to a subscription on this one,
+    /// treating `*` and `>` tokens of this subject as wildcards and every token of the given
+    /// subject literally, as the server does
+    ///
+    /// ```rust
+    /// extern crate nats_types;
+    ///
+    /// use nats_types::Subject;
+    ///
+    /// let pattern: Subject = "orders.*.created".parse().unwrap();
+    /// assert!(pattern.matches(&"orders.eu.created".parse().unwrap()));
+    /// assert!(!pattern.matches(&"orders.eu.west.created".parse().unwrap()));
+    ///
+    /// let pattern: Subject = "telemetry.>".parse().unwrap();
+    /// assert!(pattern.matches(&"telemetry.cpu.load".parse().unwrap()));
+    /// assert!(!pattern.matches(&"telemetry".parse().unwrap()));
+    /// ```
+    pub fn matches(&self, subject: &Subject) -> bool {
+        let mut pattern = self.tokens();
+        let mut literal = subject.tokens();
+        loop {
+            match (pattern.next(), literal.next()) {
+                // validation guarantees `>` is the last token
+                (Some(">"), Some(_)) => return true,
+                (Some("*"), Some(_)) => {}
+                (Some(p), Some(l)) if p == l => {}
+                (None, None) => return true,
+                _ => return false,
+            }
+        }
+    }
+}
+
+// Validates a subject that may be published to, without taking ownership of it
+pub(crate) fn validate_literal(subject: &str) -> Result<(), NatsParseError> {
+    validate(subject)?;
+    if subject.split('.').any(|t| t == "*" || t == ">") {
+        return Err(NatsParseError::new(
+            ParseErrorKind::InvalidSubject,
+            format!("Invalid subject '{}' - wildcards are not allowed", subject),
+        ));
+    }
+    Ok(())
+}
+
+// Validates the subject and reply subject of a message about to be published
+pub(crate) fn validate_publish(
+    subject: &Subject,
+    reply_to: Option<&str>,
+) -> Result<(), NatsParseError> {
+    validate_literal(subject.as_str())?;
+    match reply_to {
+        Some(rt) => validate_literal(rt),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn validate_queue_group(queue_group: &str) -> Result<(), NatsParseError> {
+    if queue_group.is_empty() || queue_group.contains(char::is_whitespace) {
+        return Err(NatsParseError::new(
+            ParseErrorKind::InvalidSubject,
+            format!(
+                "Invalid queue group {:?} - queue groups may not be empty or contain whitespace",
+                queue_group
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn validate(subject: &str) -> Result<(), NatsParseError> {
+    let invalid = |offset: usize, reason: &str| {
+        Err(NatsParseError::new(
+            ParseErrorKind::InvalidSubject,
+            format!("Invalid subject '{}' - {}", subject, reason),
+        )
+        .at(offset))
+    };
+    if subject.is_empty() {
+        return invalid(0, "subject is empty");
+    }
+    if let Some(pos) = subject.find(char::is_whitespace) {
+        return invalid(pos, "contains whitespace");
+    }
+    let mut offset = 0;
+    let mut tokens = subject.split('.').peekable();
+    while let Some(token) = tokens.next() {
+        if token.is_empty() {
+            return invalid(offset, "contains an empty token");
+        }
+        if token == ">" && tokens.peek().is_some() {
+            return invalid(offset, "'>' must be the last token");
+        }
+        offset += token.len() + 1;
+    }
+    Ok(())
+}
+
+/// An iterator over the tokens of a `Subject`, created by `Subject::tokens`
+#[derive(Debug, Clone)]
+pub struct Tokens<'a>(std::str::Split<'a, char>);
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        self.0.next()
+    }
+}
+
+impl<'a> DoubleEndedIterator for Tokens<'a> {
+    fn next_back(&mut self) -> Option<&'a str> {
+        self.0.next_back()
+    }
+}
+
+impl Display for Subject {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Subject {
+    type Err = NatsParseError;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        Subject::new(s)
+    }
+}
+
+impl TryFrom<String> for Subject {
+    type Error = NatsParseError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Subject::new(s)
+    }
+}
+
+impl TryFrom<&str> for Subject {
+    type Error = NatsParseError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        Subject::new(s)
+    }
+}
+
+impl From<Subject> for String {
+    fn from(s: Subject) -> Self {
+        s.0
+    }
+}
+
+impl AsRef<str> for Subject {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Subject {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for Subject {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Subject {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Subject;
+    use crate::ParseErrorKind;
+    use proptest::prelude::*;
+
+    #[test]
+    fn valid_subjects() {
+        for s in &[
+            "foo",
+            "foo.bar",
+            "foo.*.baz",
+            "foo.>",
+            ">",
+            "*",
+            "foo*.b>r",
+            "$JS.API.INFO",
+        ] {
+            assert!(Subject::new(*s).is_ok(), "{}", s);
+        }
+        assert!(Subject::literal("foo*.b>r").is_ok());
+        assert!(Subject::literal("foo.*").is_err());
+        assert!(Subject::literal("foo.>").is_err());
+    }
+
+    #[test]
+    fn invalid_subjects() {
+        for (s, offset) in &[
+            ("", 0),
+            ("foo bar", 3),
+            ("foo\tbar", 3),
+            ("foo..bar", 4),
+            (".foo", 0),
+            ("foo.", 4),
+            ("foo.>.bar", 4),
+        ] {
+            let err = Subject::new(*s).unwrap_err();
+            assert_eq!(err.kind(), ParseErrorKind::InvalidSubject, "{}", s);
+            assert_eq!(err.offset(), *offset, "{}", s);
+        }
+    }
+
+    #[test]
+    fn tokens() {
+        let subject = Subject::new("a.bb.ccc").unwrap();
+        assert_eq!(subject.tokens().collect::<Vec<_>>(), ["a", "bb", "ccc"]);
+        assert_eq!(subject.tokens().next_back(), Some("ccc"));
+        assert!(!subject.is_wildcard());
+        assert_eq!(subject, "a.bb.ccc");
+    }
+
+    #[test]
+    fn wildcard_matching() {
+        let cases = [
+            ("foo.bar", "foo.bar", true),
+            ("foo.bar", "foo.baz", false),
+            ("foo.*", "foo.bar", true),
+            ("foo.*", "foo.bar.baz", false),
+            ("foo.*", "foo", false),
+            ("*.bar", "foo.bar", true),
+            ("*", "foo", true),
+            ("foo.>", "foo.bar.baz", true),
+            ("foo.>", "foo", false),
+            (">", "foo.bar", true),
+            ("foo.*.>", "foo.bar", false),
+            ("foo.*.>", "foo.bar.baz", true),
+            ("foo*", "foox", false),
+            // wildcards in the subject being matched are ordinary characters
+            ("foo.bar", "foo.*", false),
+            ("foo.*", "foo.*", true),
+        ];
+        for (pattern, subject, expected) in &cases {
+            let pattern = Subject::new(*pattern).unwrap();
+            let subject = Subject::new(*subject).unwrap();
+            assert_eq!(
+                pattern.matches(&subject),
+                *expected,
+                "{} {}",
+                pattern,
+                subject
+            );
+        }
+    }
+
+    // A port of the server's character-based `matchLiteral`, used as a reference
+    fn reference_match(literal: &str, subject: &str) -> bool {
+        let literal = literal.as_bytes();
+        let subject = subject.as_bytes();
+        let (ll, ls) = (literal.len(), subject.len());
+        let mut li = 0;
+        let mut i = 0;
+        while i < ls {
+            if li >= ll {
+                return false;
+            }
+            match subject[i] {
+                b'*' if i == 0 || subject[i - 1] == b'.' => {
+                    if i == ls - 1 {
+                        loop {
+                            if li >= ll {
+                                return true;
+                            }
+                            if literal[li] == b'.' {
+                                return false;
+                            }
+                            li += 1;
+                        }
+                    } else if subject[i + 1] == b'.' {
+                        loop {
+                            if li >= ll {
+                                return false;
+                            }
+                            if literal[li] == b'.' {
+                                break;
+                            }
+                            li += 1;
+                        }
+                        i += 1;
+                    }
+                }
+                b'>' if (i == 0 || subject[i - 1] == b'.') && i == ls - 1 => return true,
+                _ => {}
+            }
+            if subject[i] != literal[li] {
+                return false;
+            }
+            li += 1;
+            i += 1;
+        }
+        li >= ll
+    }
+
+    fn subject_strategy(tokens: &'static [&'static str]) -> impl Strategy<Value = Subject> {
+        prop::collection::vec(prop::sample::select(tokens), 1..6)
+            .prop_filter_map("invalid subject", |t| Subject::new(t.join(".")).ok())
+    }
+
+    proptest! {
+        #[test]
+        fn matches_agree_with_server(
+            pattern in subject_strategy(&["a", "b", "ab", "a*", "*", ">"]),
+            literal in subject_strategy(&["a", "b", "ab", "a*", "*", ">"]),
+        ) {
+            prop_assert_eq!(
+                pattern.matches(&literal),
+                reference_match(literal.as_str(), pattern.as_str())
+            );
+        }
+
+        #[test]
+        fn patterns_match_their_instances(
+            pattern in subject_strategy(&["a", "b", "*", ">"]),
+            fill in prop::collection::vec(prop::sample::select(&["x", "y.z"][..]), 6),
+        ) {
+            let mut fill = fill.into_iter();
+            let literal: Vec<String> = pattern
+                .tokens()
+                .map(|t| match t {
+                    "*" => "x".to_string(),
+                    ">" => fill.next().unwrap().to_string(),
+                    t => t.to_string(),
+                })
+                .collect();
+            let literal = Subject::new(literal.join(".")).unwrap();
+            prop_assert!(pattern.matches(&literal));
+        }
+    }
+}
@@ -0,0 +1,378 @@
+use crate::{Subject, SubscribeMessage};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+// the number of distinct subjects whose matches are remembered
+const CACHE_SIZE: usize = 1024;
+
+/// A trie of subscriptions keyed by the tokens of their subjects, used to find every
+/// subscription interested in a subject without scanning them all. Each subscription is
+/// identified by its SID and carries a value of type `T`, such as a channel to deliver to.
+///
+/// The results of recent lookups are cached, and the cache is kept up to date as
+/// subscriptions are added and removed.
+///
+/// ```rust
+/// extern crate nats_types;
+///
+/// use nats_types::{Sublist, SubscribeMessage};
+///
+/// let mut sublist = Sublist::new();
+/// let sub = |subject: &str, queue_group: Option<&str>, sid| {
+///     let queue_group = queue_group.map(str::to_string);
+///     SubscribeMessage::new(subject.parse().unwrap(), queue_group, sid).unwrap()
+/// };
+/// sublist.insert(&sub("orders.*", None, 1), "audit");
+/// sublist.insert(&sub("orders.>", Some("workers"), 2), "w1");
+/// sublist.insert(&sub("orders.>", Some("workers"), 3), "w2");
+///
+/// let matches = sublist.matches(&"orders.new".parse().unwrap());
+/// assert_eq!(matches.subscriptions, [&"audit"]);
+/// assert_eq!(matches.queue_groups[0].0, "workers");
+/// assert_eq!(matches.queue_groups[0].1.len(), 2);
+/// ```
+#[derive(Debug)]
+pub struct Sublist<T> {
+    root: Level,
+    entries: HashMap<usize, Entry<T>>,
+    cache: Mutex<HashMap<Subject, Arc<Vec<usize>>>>,
+}
+
+#[derive(Debug)]
+struct Entry<T> {
+    subject: Subject,
+    queue_group: Option<String>,
+    value: T,
+}
+
+#[derive(Debug, Default)]
+struct Level {
+    literals: HashMap<String, Node>,
+    pwc: Option<Box<Node>>,
+    fwc: Option<Box<Node>>,
+}
+
+#[derive(Debug, Default)]
+struct Node {
+    sids: Vec<usize>,
+    next: Option<Box<Level>>,
+}
+
+impl Level {
+    fn is_empty(&self) -> bool {
+        self.literals.is_empty() && self.pwc.is_none() && self.fwc.is_none()
+    }
+
+    fn node_mut(&mut self, token: &str) -> &mut Node {
+        match token {
+            "*" => self.pwc.get_or_insert_with(Box::default),
+            ">" => self.fwc.get_or_insert_with(Box::default),
+            _ => self.literals.entry(token.to_string()).or_default(),
+        }
+    }
+
+    // removes the SID from the node for the remaining tokens, pruning nodes left empty
+    fn remove(&mut self, tokens: &[&str], sid: usize) {
+        let (token, rest) = match tokens.split_first() {
+            Some(split) => split,
+            None => return,
+        };
+        let node = match *token {
+            "*" => self.pwc.as_deref_mut(),
+            ">" => self.fwc.as_deref_mut(),
+            _ => self.literals.get_mut(*token),
+        };
+        let node = match node {
+            Some(node) => node,
+            None => return,
+        };
+        if rest.is_empty() {
+            node.sids.retain(|s| *s != sid);
+        } else if let Some(next) = node.next.as_mut() {
+            next.remove(rest, sid);
+            if next.is_empty() {
+                node.next = None;
+            }
+        }
+        if node.sids.is_empty() && node.next.is_none() {
+            match *token {
+                "*" => self.pwc = None,
+                ">" => self.fwc = None,
+                _ => {
+                    self.literals.remove(*token);
+                }
+            }
+        }
+    }
+
+    fn collect(&self, tokens: &[&str], out: &mut Vec<usize>) {
+        let (token, rest) = match tokens.split_first() {
+            Some(split) => split,
+            None => return,
+        };
+        if let Some(fwc) = &self.fwc {
+            out.extend_from_slice(&fwc.sids);
+        }
+        let nodes = [self.literals.get(*token), self.pwc.as_deref()];
+        for node in nodes.iter().flatten() {
+            if rest.is_empty() {
+                out.extend_from_slice(&node.sids);
+            } else if let Some(next) = &node.next {
+                next.collect(rest, out);
+            }
+        }
+    }
+}
+
+/// The subscriptions in a `Sublist` interested in a subject. A message should be delivered to
+/// every plain subscription and to one member of each queue group.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matches<'a, T> {
+    /// The interested subscriptions that are not part of a queue group
+    pub subscriptions: Vec<&'a T>,
+    /// The members of each interested queue group, ordered by queue group name
+    pub queue_groups: Vec<(&'a str, Vec<&'a T>)>,
+}
+
+impl<'a, T> Matches<'a, T> {
+    /// Indicates whether nothing is interested in the subject
+    pub fn is_empty(&self) -> bool {
+        self.subscriptions.is_empty() && self.queue_groups.is_empty()
+    }
+}
+
+impl<T> Default for Sublist<T> {
+    fn default() -> Self {
+        Sublist {
+            root: Level::default(),
+            entries: HashMap::new(),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<T> Sublist<T> {
+    /// Creates an empty sublist
+    pub fn new() -> Sublist<T> {
+        Sublist::default()
+    }
+
+    /// Adds the subscription with the given value, replacing and returning the value of any
+    /// existing subscription with the same SID
+    pub fn insert(&mut self, sub: &SubscribeMessage, value: T) -> Option<T> {
+        let previous = self.remove(sub.subscription_id);
+        let mut level = &mut self.root;
+        let mut tokens = sub.subject.tokens().peekable();
+        while let Some(token) = tokens.next() {
+            let node = level.node_mut(token);
+            if tokens.peek().is_none() {
+                node.sids.push(sub.subscription_id);
+                break;
+            }
+            level = node.next.get_or_insert_with(Box::default);
+        }
+        self.invalidate(&sub.subject);
+        self.entries.insert(
+            sub.subscription_id,
+            Entry {
+                subject: sub.subject.clone(),
+                queue_group: sub.queue_group.clone(),
+                value,
+            },
+        );
+        previous
+    }
+
+    /// Removes the subscription with the given SID, returning its value
+    pub fn remove(&mut self, sid: usize) -> Option<T> {
+        let entry = self.entries.remove(&sid)?;
+        let tokens: Vec<&str> = entry.subject.tokens().collect();
+        self.root.remove(&tokens, sid);
+        self.invalidate(&entry.subject);
+        Some(entry.value)
+    }
+
+    /// The value of the subscription with the given SID
+    pub fn get(&self, sid: usize) -> Option<&T> {
+        self.entries.get(&sid).map(|e| &e.value)
+    }
+
+    /// The number of subscriptions
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Indicates whether there are no subscriptions
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Finds the subscriptions interested in a message published to the given subject
+    pub fn matches(&self, subject: &Subject) -> Matches<'_, T> {
+        let sids = self.match_sids(subject);
+        let mut matches = Matches {
+            subscriptions: Vec::new(),
+            queue_groups: Vec::new(),
+        };
+        for entry in sids.iter().filter_map(|sid| self.entries.get(sid)) {
+            match &entry.queue_group {
+                None => matches.subscriptions.push(&entry.value),
+                Some(queue) => match matches
+                    .queue_groups
+                    .binary_search_by(|(q, _)| (*q).cmp(queue.as_str()))
+                {
+                    Ok(i) => matches.queue_groups[i].1.push(&entry.value),
+                    Err(i) => matches
+                        .queue_groups
+                        .insert(i, (queue.as_str(), vec![&entry.value])),
+                },
+            }
+        }
+        matches
+    }
+
+    fn match_sids(&self, subject: &Subject) -> Arc<Vec<usize>> {
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(sids) = cache.get(subject) {
+            return sids.clone();
+        }
+        let tokens: Vec<&str> = subject.tokens().collect();
+        let mut sids = Vec::new();
+        self.root.collect(&tokens, &mut sids);
+        let sids = Arc::new(sids);
+        if cache.len() >= CACHE_SIZE {
+            cache.clear();
+        }
+        cache.insert(subject.clone(), sids.clone());
+        sids
+    }
+
+    // drops the cached results for every subject the changed subscription is interested in
+    fn invalidate(&mut self, pattern: &Subject) {
+        let cache = self.cache.get_mut().unwrap_or_else(|e| e.into_inner());
+        cache.retain(|subject, _| !pattern.matches(subject));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Sublist;
+    use crate::{Subject, SubscribeMessage};
+    use proptest::prelude::*;
+
+    fn sub(subject: &str, queue_group: Option<&str>, sid: usize) -> SubscribeMessage {
+        SubscribeMessage::new(
+            subject.parse().unwrap(),
+            queue_group.map(|q| q.to_string()),
+            sid,
+        )
+        .unwrap()
+    }
+
+    fn subject(s: &str) -> Subject {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn insert_match_remove() {
+        let mut sublist = Sublist::new();
+        sublist.insert(&sub("foo.bar", None, 1), 1);
+        sublist.insert(&sub("foo.*", None, 2), 2);
+        sublist.insert(&sub("foo.>", None, 3), 3);
+        sublist.insert(&sub(">", None, 4), 4);
+        sublist.insert(&sub("foo.*.baz", None, 5), 5);
+        assert_eq!(sublist.len(), 5);
+
+        let mut found: Vec<i32> = sublist
+            .matches(&subject("foo.bar"))
+            .subscriptions
+            .into_iter()
+            .cloned()
+            .collect();
+        found.sort_unstable();
+        assert_eq!(found, [1, 2, 3, 4]);
+        assert_eq!(
+            sublist.matches(&subject("foo.bar.baz")).subscriptions.len(),
+            3
+        );
+        assert_eq!(sublist.matches(&subject("foo")).subscriptions, [&4]);
+
+        assert_eq!(sublist.remove(4), Some(4));
+        assert_eq!(sublist.remove(4), None);
+        assert!(sublist.matches(&subject("foo")).is_empty());
+        assert_eq!(sublist.matches(&subject("foo.bar")).subscriptions.len(), 3);
+
+        for sid in &[1, 2, 3, 5] {
+            sublist.remove(*sid);
+        }
+        assert!(sublist.is_empty());
+        assert!(sublist.root.is_empty());
+    }
+
+    #[test]
+    fn queue_groups() {
+        let mut sublist = Sublist::new();
+        sublist.insert(&sub("jobs.*", Some("workers"), 1), "w1");
+        sublist.insert(&sub("jobs.>", Some("workers"), 2), "w2");
+        sublist.insert(&sub("jobs.new", Some("auditors"), 3), "a1");
+        sublist.insert(&sub("jobs.new", None, 4), "plain");
+
+        let matches = sublist.matches(&subject("jobs.new"));
+        assert_eq!(matches.subscriptions, [&"plain"]);
+        assert_eq!(matches.queue_groups.len(), 2);
+        assert_eq!(matches.queue_groups[0], ("auditors", vec![&"a1"]));
+        assert_eq!(matches.queue_groups[1].0, "workers");
+        assert_eq!(matches.queue_groups[1].1.len(), 2);
+    }
+
+    #[test]
+    fn replace_by_sid_invalidates_cache() {
+        let mut sublist = Sublist::new();
+        assert_eq!(sublist.insert(&sub("foo", None, 1), "a"), None);
+        assert_eq!(sublist.matches(&subject("foo")).subscriptions, [&"a"]);
+        assert_eq!(sublist.insert(&sub("bar", None, 1), "b"), Some("a"));
+        assert!(sublist.matches(&subject("foo")).is_empty());
+        assert_eq!(sublist.matches(&subject("bar")).subscriptions, [&"b"]);
+        assert_eq!(sublist.get(1), Some(&"b"));
+    }
+
+    fn subject_strategy(tokens: &'static [&'static str]) -> impl Strategy<Value = Subject> {
+        prop::collection::vec(prop::sample::select(tokens), 1..5)
+            .prop_filter_map("invalid subject", |t| Subject::new(t.join(".")).ok())
+    }
+
+    proptest! {
+        #[test]
+        fn matches_agree_with_linear_scan(
+            patterns in prop::collection::vec(subject_strategy(&["a", "b", "*", ">"]), 0..20),
+            removed in prop::collection::vec(any::<bool>(), 20),
+            literals in prop::collection::vec(subject_strategy(&["a", "b", "c"]), 1..10),
+        ) {
+            let mut sublist = Sublist::new();
+            for (sid, pattern) in patterns.iter().enumerate() {
+                sublist.insert(&SubscribeMessage::new(pattern.clone(), None, sid).unwrap(), sid);
+            }
+            for literal in &literals {
+                // populate the cache before removing
+                sublist.matches(literal);
+            }
+            for (sid, remove) in removed.iter().enumerate().take(patterns.len()) {
+                if *remove {
+                    sublist.remove(sid);
+                }
+            }
+            for literal in &literals {
+                let mut found: Vec<usize> =
+                    sublist.matches(literal).subscriptions.into_iter().cloned().collect();
+                found.sort_unstable();
+                let expected: Vec<usize> = patterns
+                    .iter()
+                    .enumerate()
+                    .filter(|(sid, p)| !removed[*sid] && p.matches(literal))
+                    .map(|(sid, _)| sid)
+                    .collect();
+                prop_assert_eq!(found, expected);
+            }
+        }
+    }
+}